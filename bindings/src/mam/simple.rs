@@ -41,6 +41,10 @@ pub fn iota_mam_create(
     index: usize,
     security: u8,
 ) -> *const CTrits {
+    let security = match iota_mam::Security::from_level(security as usize) {
+        Ok(security) => security,
+        Err(_) => return ptr::null(),
+    };
     let mut c1 = CpuCurl::<Trit>::default();
     let mut c2 = CpuCurl::<Trit>::default();
     let mut b1 = CpuCurl::<BCTrit>::default();
@@ -53,10 +57,10 @@ pub fn iota_mam_create(
             message_trits.len(),
             siblings_trits.len(),
             index,
-            security as usize,
+            security,
         ) as i64) as usize
     ];
-    iota_mam::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+    let created = iota_mam::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
         ctrits_slice_trits(seed),
         message_trits,
         ctrits_slice_trits(key),
@@ -71,18 +75,24 @@ pub fn iota_mam_create(
         &mut c2,
         &mut b1,
     );
+    if created.is_err() {
+        return ptr::null();
+    }
 
     Box::into_raw(Box::new(ctrits_from_trits(out)))
 }
 
+/// Parses a signed `payload`. Unsigned payloads, which anyone holding the
+/// side key can make, are rejected with `MamError::SecurityTooLow`.
 #[no_mangle]
 pub fn iota_mam_parse(payload: &mut CTrits, side_key: &CTrits, root: &CTrits) -> *mut ParseResult {
     let mut c1 = CpuCurl::<Trit>::default();
     let mut payload_trits = ctrits_slice_trits_mut(payload);
-    let result = iota_mam::parse(
+    let result = iota_mam::parse_min_security(
         &mut payload_trits,
         ctrits_slice_trits(side_key),
         ctrits_slice_trits(root),
+        iota_mam::Security::Low,
         &mut c1,
     );
 
@@ -92,13 +102,9 @@ pub fn iota_mam_parse(payload: &mut CTrits, side_key: &CTrits, root: &CTrits) ->
             message: ptr::null_mut(),
             next: ptr::null_mut(),
         })),
-        Ok((start, end)) => {
-            let message = Box::new(ctrits_from_trits(
-                payload_trits[start + HASH_LENGTH..end].to_vec(),
-            ));
-            let next = Box::new(ctrits_from_trits(
-                payload_trits[start..start + HASH_LENGTH].to_vec(),
-            ));
+        Ok(parsed) => {
+            let message = Box::new(ctrits_from_trits(parsed.message(payload_trits).to_vec()));
+            let next = Box::new(ctrits_from_trits(parsed.next(payload_trits).to_vec()));
             Box::into_raw(Box::new(ParseResult {
                 error: iota_mam::MamError::None,
                 message: Box::into_raw(message),
@@ -214,3 +220,29 @@ pub fn it_fails() {
 
     assert!(raw.error == iota_mam::MamError::InvalidHash);
 }
+
+#[test]
+pub fn it_rejects_unsigned() {
+    let message: Vec<Trit> = "UNSIGNED".chars().flat_map(char_to_trits).cloned().collect();
+    let side_key: Vec<Trit> = "SIDEKEY".chars().flat_map(char_to_trits).cloned().collect();
+    let root: [Trit; HASH_LENGTH] = [1; HASH_LENGTH];
+    let next: [Trit; HASH_LENGTH] = [-1; HASH_LENGTH];
+    let mut payload: Vec<Trit> = vec![0; iota_mam::unsigned_min_length(message.len(), 0)];
+    let mut c1 = CpuCurl::<Trit>::default();
+    iota_mam::create_unsigned(&message, &side_key, &root, &next, 0, &mut payload, &mut c1);
+
+    let mut payload_ctrits = ctrits_from_trits(payload);
+    let root_ctrits = ctrits_from_trits(root.to_vec());
+    let side_key_ctrits = ctrits_from_trits(side_key);
+
+    let raw: Box<ParseResult> = unsafe {
+        Box::from_raw(iota_mam_parse(
+            &mut payload_ctrits,
+            &side_key_ctrits,
+            &root_ctrits,
+        ))
+    };
+
+    assert!(raw.error == iota_mam::MamError::SecurityTooLow);
+    assert!(raw.message.is_null());
+}
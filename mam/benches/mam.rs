@@ -0,0 +1,212 @@
+#[macro_use]
+extern crate criterion;
+extern crate iota_mam as mam;
+extern crate iota_merkle as merkle;
+extern crate iota_trytes as trytes;
+extern crate iota_curl as curl;
+extern crate iota_curl_cpu as curl_cpu;
+
+use criterion::Criterion;
+use curl::*;
+use curl_cpu::*;
+use trytes::*;
+use mam::{mask, sliced};
+use mam::Security;
+
+const SEED: &'static str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9";
+const SIDE_KEY: &'static str = "EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD";
+const MESSAGE: &'static str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9";
+
+/// Security levels
+const LEVELS: [usize; 3] = [1, 2, 3];
+/// Lengths of masked messages, in trits
+const LENGTHS: [usize; 4] = [81, 243, 2187, 19683];
+/// Tree sizes and security levels
+const TREES: [(usize, usize); 6] = [(1, 1), (4, 1), (16, 1), (64, 1), (4, 2), (4, 3)];
+
+/// Index of the leaf signing the benchmarked messages
+const INDEX: usize = 1;
+
+fn trits(trytes: &str) -> Vec<Trit> {
+    trytes.chars().flat_map(char_to_trits).cloned().collect()
+}
+
+/// Root of a tree of four leaves at `security`, and the siblings of the leaf
+/// at `INDEX`
+fn channel(security: Security) -> (Vec<Trit>, Vec<Trit>) {
+    let mut c1 = CpuCurl::<Trit>::default();
+    let mut c2 = CpuCurl::<Trit>::default();
+    let mut c3 = CpuCurl::<Trit>::default();
+    let tree = merkle::create(&trits(SEED), 0, 4, security.level(), &mut c1, &mut c2, &mut c3);
+    let mut root: Vec<Trit> = vec![0; HASH_LENGTH];
+    merkle::slice(&tree, &mut root);
+    let branch = merkle::branch(&tree, INDEX);
+    let siblings_length = merkle::len(&branch) * HASH_LENGTH;
+    let mut siblings: Vec<Trit> = vec![0; siblings_length];
+    merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
+    (root, siblings)
+}
+
+/// Creates a payload of `MESSAGE` at `security` into `payload`
+fn create_payload(
+    security: Security,
+    root: &[Trit],
+    siblings: &[Trit],
+    payload: &mut Vec<Trit>,
+    c1: &mut CpuCurl<Trit>,
+    c2: &mut CpuCurl<Trit>,
+    bc: &mut CpuCurl<BCTrit>,
+) {
+    let message = trits(MESSAGE);
+    payload.resize(
+        mam::min_length(message.len(), siblings.len(), INDEX, security),
+        0,
+    );
+    let length = mam::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+        &trits(SEED),
+        &message,
+        &trits(SIDE_KEY),
+        root,
+        siblings,
+        root,
+        0,
+        INDEX,
+        security,
+        payload,
+        c1,
+        c2,
+        bc,
+    ).unwrap();
+    payload.truncate(length);
+}
+
+fn create(c: &mut Criterion) {
+    c.bench_function_over_inputs(
+        "mam::create",
+        |b, &&level| {
+            let security = Security::from_level(level).unwrap();
+            let (root, siblings) = channel(security);
+            let mut payload: Vec<Trit> = Vec::new();
+            let mut c1 = CpuCurl::<Trit>::default();
+            let mut c2 = CpuCurl::<Trit>::default();
+            let mut bc = CpuCurl::<BCTrit>::default();
+            b.iter(|| {
+                create_payload(
+                    security,
+                    &root,
+                    &siblings,
+                    &mut payload,
+                    &mut c1,
+                    &mut c2,
+                    &mut bc,
+                )
+            });
+        },
+        &LEVELS,
+    );
+}
+
+fn parse(c: &mut Criterion) {
+    c.bench_function_over_inputs(
+        "mam::parse",
+        |b, &&level| {
+            let security = Security::from_level(level).unwrap();
+            let (root, siblings) = channel(security);
+            let side_key = trits(SIDE_KEY);
+            let mut payload: Vec<Trit> = Vec::new();
+            let mut c1 = CpuCurl::<Trit>::default();
+            let mut c2 = CpuCurl::<Trit>::default();
+            let mut bc = CpuCurl::<BCTrit>::default();
+            create_payload(
+                security,
+                &root,
+                &siblings,
+                &mut payload,
+                &mut c1,
+                &mut c2,
+                &mut bc,
+            );
+            b.iter_with_setup(
+                || payload.clone(),
+                |mut payload| {
+                    mam::parse(&mut payload, &side_key, &root, &mut c1).unwrap();
+                },
+            );
+        },
+        &LEVELS,
+    );
+}
+
+fn masking(c: &mut Criterion) {
+    c.bench_function_over_inputs(
+        "mask::mask",
+        |b, &&length| {
+            let key = trits(SIDE_KEY);
+            let mut payload: Vec<Trit> = vec![0; length];
+            let mut curl = CpuCurl::<Trit>::default();
+            b.iter(|| {
+                mask::mask(&mut payload, &key, &mut curl);
+                curl.reset();
+            });
+        },
+        &LENGTHS,
+    );
+    c.bench_function_over_inputs(
+        "mask::unmask",
+        |b, &&length| {
+            let key = trits(SIDE_KEY);
+            let mut payload: Vec<Trit> = vec![0; length];
+            let mut curl = CpuCurl::<Trit>::default();
+            b.iter(|| {
+                mask::unmask(&mut payload, &key, &mut curl);
+                curl.reset();
+            });
+        },
+        &LENGTHS,
+    );
+    // Unmasks `sliced::LANES` payloads per iteration
+    c.bench_function_over_inputs(
+        "sliced::unmask_many",
+        |b, &&length| {
+            let key = trits(SIDE_KEY);
+            let keys: Vec<&[Trit]> = (0..sliced::LANES).map(|_| &key[..]).collect();
+            let mut payloads: Vec<Vec<Trit>> = vec![vec![0; length]; sliced::LANES];
+            let mut curls: Vec<CpuCurl<Trit>> =
+                (0..sliced::LANES).map(|_| CpuCurl::default()).collect();
+            let mut bcurl = CpuCurl::<BCTrit>::default();
+            b.iter(|| {
+                let mut slices: Vec<&mut [Trit]> =
+                    payloads.iter_mut().map(|p| &mut p[..]).collect();
+                sliced::unmask_many(&mut slices, &keys, &mut curls, &mut bcurl);
+                for curl in curls.iter_mut() {
+                    curl.reset();
+                }
+            });
+        },
+        &LENGTHS,
+    );
+}
+
+fn trees(c: &mut Criterion) {
+    c.bench_function_over_inputs(
+        "merkle::create",
+        |b, &&(count, level)| {
+            let seed = trits(SEED);
+            let mut c1 = CpuCurl::<Trit>::default();
+            let mut c2 = CpuCurl::<Trit>::default();
+            let mut c3 = CpuCurl::<Trit>::default();
+            b.iter(|| {
+                merkle::create(&seed, 0, count, level, &mut c1, &mut c2, &mut c3)
+            });
+        },
+        &TREES,
+    );
+}
+
+criterion_group! {
+    name = benches;
+    // Signing and tree generation take long enough for few samples to do
+    config = Criterion::default().sample_size(10);
+    targets = create, parse, masking, trees
+}
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+//! Fills in the expected root, payload and parse result of every vector
+//! with a seed in a vector file, e.g. after appending a vector with only its
+//! inputs:
+//!
+//! ```text
+//! cargo run -p iota-mam --example generate_vectors -- mam/vectors/v1.txt
+//! ```
+//!
+//! Vectors without a seed are written back as they are.
+extern crate iota_mam as mam;
+extern crate iota_merkle as merkle;
+extern crate iota_trytes as trytes;
+extern crate iota_curl as curl;
+extern crate iota_curl_cpu as curl_cpu;
+extern crate iota_sign as sign;
+extern crate iota_pascal as pascal;
+
+#[path = "../vectors/format.rs"]
+mod format;
+
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+
+fn main() {
+    let path = env::args().nth(1).expect(
+        "usage: generate_vectors <vector file>",
+    );
+    let mut text = String::new();
+    File::open(&path)
+        .and_then(|mut f| f.read_to_string(&mut text))
+        .unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let mut file = format::read(&text).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    if file.version != format::VERSION {
+        panic!("{}: version {} is not {}", path, file.version, format::VERSION);
+    }
+
+    for vector in file.vectors.iter_mut() {
+        if vector.get("seed").is_none() {
+            continue;
+        }
+        let created = format::create(vector).unwrap_or_else(|e| panic!("{}", e));
+        let result = {
+            let side_key = vector.get("side_key").unwrap_or("");
+            format::parse(side_key, &created.root, &created.payload).result
+        };
+        println!("{}: {}", vector.name(), result);
+        vector.set("root", created.root);
+        vector.set("payload", created.payload);
+        vector.set("result", result);
+    }
+
+    File::create(&path)
+        .and_then(|mut f| f.write_all(format::write(&file).as_bytes()))
+        .unwrap_or_else(|e| panic!("{}: {}", path, e));
+}
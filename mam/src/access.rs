@@ -0,0 +1,576 @@
+//! Per-subscriber access control
+//!
+//! Every message is masked with its own content key, which is then wrapped
+//! once for each authorized subscriber key. Revoking a subscriber only means
+//! no longer wrapping content keys for it; nobody else needs to be re-keyed.
+//!
+//! The wrapped keys are prepended to the MAM payload:
+//!
+//! ```text
+//! [
+//!     Encoded Number of Subscribers,
+//!     [Subscriber Id, masked[Content Key]] ...,
+//!     MAM Payload
+//! ]
+//! ```
+use alloc::Vec;
+use curl::*;
+use trytes::*;
+use mask::*;
+use errors::*;
+use ct::*;
+use security::*;
+use keys;
+use mam::{self, Version};
+use pascal::{Reader, Writer};
+use wipe::*;
+
+/// Length of the identifier telling a subscriber which entry is theirs
+pub const ID_LENGTH: usize = HASH_LENGTH / 3;
+/// Length of a single wrapped key entry
+pub const ENTRY_LENGTH: usize = ID_LENGTH + keys::KEY_LENGTH;
+
+/// The set of subscriber keys content keys are wrapped for
+#[derive(Default)]
+pub struct Subscribers {
+    keys: Vec<Vec<Trit>>,
+}
+
+impl Subscribers {
+    pub fn new() -> Self {
+        Subscribers { keys: Vec::new() }
+    }
+
+    /// Authorizes a subscriber `key`. Adding a key twice has no effect.
+    pub fn add(&mut self, key: &[Trit]) -> Result<(), MamError> {
+        keys::validate(key)?;
+        if !self.contains(key) {
+            self.keys.push(key.to_vec());
+        }
+        Ok(())
+    }
+
+    /// Revokes a subscriber `key`, returning whether it was authorized
+    pub fn revoke(&mut self, key: &[Trit]) -> bool {
+        let len = self.keys.len();
+        self.keys.retain(|k| &k[..] != key);
+        len != self.keys.len()
+    }
+
+    pub fn contains(&self, key: &[Trit]) -> bool {
+        self.keys.iter().any(|k| &k[..] == key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Length of the wrapped key block for the current subscribers
+    pub fn wrapped_length(&self) -> usize {
+        mam::number_length(self.keys.len()) + self.keys.len() * ENTRY_LENGTH
+    }
+
+    /// Wraps the `content_key` of the message at `index` under `root` for every
+    /// subscriber, writing the block to `out`.
+    ///
+    /// Returns the number of trits written
+    pub fn wrap<C: Curl<Trit>>(
+        &self,
+        content_key: &[Trit],
+        root: &[Trit],
+        index: usize,
+        out: &mut [Trit],
+        curl: &mut C,
+    ) -> usize {
+        let count_end = mam::number_length(self.keys.len());
+        let wrapped_length = self.wrapped_length();
+        assert!(
+            out.len() >= wrapped_length,
+            "should be: {}, is {}",
+            wrapped_length,
+            out.len()
+        );
+        mam::write_number(self.keys.len(), &mut Writer::new(&mut out[..count_end]));
+        for (key, entry) in self.keys.iter().zip(
+            out[count_end..wrapped_length].chunks_mut(ENTRY_LENGTH),
+        )
+        {
+            let (id, wrapped) = entry.split_at_mut(ID_LENGTH);
+            mam::id(key, root, id, curl);
+            wrapped.clone_from_slice(content_key);
+            absorb_index(root, index, curl);
+            mask(wrapped, key, curl);
+            curl.reset();
+        }
+        wrapped_length
+    }
+}
+
+fn absorb_index<C: Curl<Trit>>(root: &[Trit], index: usize, curl: &mut C) {
+    let mut encoded_index: Vec<Trit> = vec![0; mam::number_length(index)];
+    mam::write_number(index, &mut Writer::new(&mut encoded_index));
+    curl.absorb(root);
+    curl.absorb(&encoded_index);
+}
+
+/// Derives the content key of the message at `index` under `root` from the
+/// publisher's `seed`, writing it to `out`
+pub fn content_key<C: Curl<Trit>>(
+    seed: &[Trit],
+    root: &[Trit],
+    index: usize,
+    out: &mut [Trit],
+    curl: &mut C,
+) {
+    curl.absorb(seed);
+    absorb_index(root, index, curl);
+    curl.squeeze(out);
+    curl.reset();
+}
+
+/// Unwraps the content key meant for `subscriber_key` from a `wrapped` key block
+/// of the message at `index` under `root`, writing it to `out`.
+///
+/// Returns the length of the wrapped key block, `MamError::NotSubscribed`
+/// if the block holds no entry for this subscriber, or
+/// `MamError::ArrayOutOfBounds` if the block is malformed or `out` is not
+/// `keys::KEY_LENGTH` long
+pub fn unwrap<C: Curl<Trit>>(
+    wrapped: &[Trit],
+    subscriber_key: &[Trit],
+    root: &[Trit],
+    index: usize,
+    out: &mut [Trit],
+    curl: &mut C,
+) -> Result<usize, MamError> {
+    if out.len() != keys::KEY_LENGTH {
+        return Err(MamError::ArrayOutOfBounds);
+    }
+    let mut reader = Reader::new(wrapped);
+    let count = Version::Compact.read(&mut reader)?;
+    if count > reader.remaining() / ENTRY_LENGTH {
+        return Err(MamError::ArrayOutOfBounds);
+    }
+    let entries = reader.read_trits(count * ENTRY_LENGTH)?;
+    let wrapped_end = reader.position();
+    let mut id: [Trit; ID_LENGTH] = [0; ID_LENGTH];
+    mam::id(subscriber_key, root, &mut id, curl);
+    match entries.chunks(ENTRY_LENGTH).find(
+        |entry| ct_eq(&entry[..ID_LENGTH], &id),
+    ) {
+        Some(entry) => {
+            out.clone_from_slice(&entry[ID_LENGTH..]);
+            absorb_index(root, index, curl);
+            unmask(out, subscriber_key, curl);
+            curl.reset();
+            Ok(wrapped_end)
+        }
+        None => Err(MamError::NotSubscribed),
+    }
+}
+
+/// Creates a payload whose content key is wrapped for every one of the
+/// `subscribers`, followed by the MAM payload as made by `mam::create`.
+///
+/// Takes the same parameters as `mam::create`, minus the side key, which is
+/// derived per message with `content_key`.
+///
+/// Returns the length of the payload, or `MamError::NonceSearchFailed` if
+/// no hamming nonce was found
+pub fn create<C, CB, H>(
+    subscribers: &Subscribers,
+    seed: &[Trit],
+    message: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next: &[Trit],
+    start: isize,
+    index: usize,
+    security: Security,
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+{
+    let mut key: [Trit; keys::KEY_LENGTH] = [0; keys::KEY_LENGTH];
+    content_key(seed, root, index, &mut key, curl);
+    let wrapped_end = subscribers.wrap(&key, root, index, payload, curl);
+    let payload_length = mam::create::<C, CB, H>(
+        seed,
+        message,
+        &key,
+        root,
+        siblings,
+        next,
+        start,
+        index,
+        security,
+        &mut payload[wrapped_end..],
+        curl,
+        encr_curl,
+        bcurl,
+    )?;
+    Ok(wrapped_end + payload_length)
+}
+
+/// Minimum length of a payload made by `create`
+pub fn min_length(
+    subscribers: &Subscribers,
+    message_length: usize,
+    siblings_length: usize,
+    index: usize,
+    security: Security,
+) -> usize {
+    subscribers.wrapped_length() + mam::min_length(message_length, siblings_length, index, security)
+}
+
+/// Parses a `payload` made by `create` as the subscriber holding
+/// `subscriber_key`, for the message at `index` under `root`.
+///
+/// Every subscriber holds the content key, so unsigned payloads are rejected
+/// with `MamError::SecurityTooLow`, and payloads signed for another index
+/// with `MamError::IndexMismatch`.
+///
+/// Returns the same as `mam::parse`, relative to the start of `payload`
+pub fn parse<C: Curl<Trit>>(
+    payload: &mut [Trit],
+    subscriber_key: &[Trit],
+    root: &[Trit],
+    index: usize,
+    curl: &mut C,
+) -> Result<mam::Parsed, MamError> {
+    let mut key: [Trit; keys::KEY_LENGTH] = [0; keys::KEY_LENGTH];
+    let wrapped_end = unwrap(payload, subscriber_key, root, index, &mut key, curl)?;
+    let parsed = mam::parse_min_security(
+        &mut payload[wrapped_end..],
+        &key,
+        root,
+        Security::Low,
+        curl,
+    );
+    wipe(&mut key);
+    let parsed = parsed?;
+    if parsed.index != index {
+        return Err(MamError::IndexMismatch);
+    }
+    Ok(mam::Parsed {
+        start: wrapped_end + parsed.start,
+        end: wrapped_end + parsed.end,
+        ..parsed
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+    use merkle;
+
+    fn trits(s: &str) -> Vec<Trit> {
+        s.chars().flat_map(char_to_trits).cloned().collect()
+    }
+
+    /// Publishes `message` at `index` of a two leaf tree for `subscribers`,
+    /// returning the root of the tree and the payload
+    fn publish(
+        subscribers: &Subscribers,
+        message: &[Trit],
+        index: usize,
+    ) -> ([Trit; HASH_LENGTH], Vec<Trit>) {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let security = Security::Low;
+        let start: isize = 0;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let tree = merkle::create(&seed, start, 2, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&tree, &mut root);
+        let branch = merkle::branch(&tree, index);
+        let siblings_length = merkle::len(&branch) * HASH_LENGTH;
+        let mut siblings: Vec<Trit> = vec![0; siblings_length];
+        merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
+
+        let mut payload: Vec<Trit> =
+            vec![0; min_length(subscribers, message.len(), siblings.len(), index, security)];
+        create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            subscribers,
+            &seed,
+            message,
+            &root,
+            &siblings,
+            &root,
+            start,
+            index,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+        (root, payload)
+    }
+
+    #[test]
+    fn only_subscribers_unwrap() {
+        let alice = trits("ALICEALICEALICE");
+        let bob = trits("BOBBOBBOBBOBBOB");
+        let eve = trits("EVEEVEEVEEVEEVE");
+        let root = trits("MYMERKLEROOTHASH9");
+        let index = 3;
+        let mut curl = CpuCurl::<Trit>::default();
+        let mut key: [Trit; keys::KEY_LENGTH] = [0; keys::KEY_LENGTH];
+        content_key(&trits("SEED"), &root, index, &mut key, &mut curl);
+
+        let mut subscribers = Subscribers::new();
+        subscribers.add(&alice).unwrap();
+        subscribers.add(&bob).unwrap();
+        subscribers.add(&bob).unwrap();
+        assert_eq!(subscribers.len(), 2);
+
+        let mut wrapped: Vec<Trit> = vec![0; subscribers.wrapped_length()];
+        subscribers.wrap(&key, &root, index, &mut wrapped, &mut curl);
+
+        let mut out: [Trit; keys::KEY_LENGTH] = [0; keys::KEY_LENGTH];
+        for subscriber in [&alice, &bob].iter() {
+            assert_eq!(
+                unwrap(&wrapped, subscriber, &root, index, &mut out, &mut curl),
+                Ok(wrapped.len())
+            );
+            assert_eq!(&out[..], &key[..]);
+        }
+        assert_eq!(
+            unwrap(&wrapped, &eve, &root, index, &mut out, &mut curl),
+            Err(MamError::NotSubscribed)
+        );
+
+        assert!(subscribers.revoke(&alice));
+        assert!(!subscribers.revoke(&alice));
+        let mut wrapped: Vec<Trit> = vec![0; subscribers.wrapped_length()];
+        subscribers.wrap(&key, &root, index, &mut wrapped, &mut curl);
+        assert_eq!(
+            unwrap(&wrapped, &alice, &root, index, &mut out, &mut curl),
+            Err(MamError::NotSubscribed)
+        );
+        assert!(unwrap(&wrapped, &bob, &root, index, &mut out, &mut curl).is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_blocks() {
+        let alice = trits("ALICEALICEALICE");
+        let root = trits("MYMERKLEROOTHASH9");
+        let index = 3;
+        let mut curl = CpuCurl::<Trit>::default();
+        let mut out: [Trit; keys::KEY_LENGTH] = [0; keys::KEY_LENGTH];
+
+        let mut subscribers = Subscribers::new();
+        subscribers.add(&alice).unwrap();
+        let mut wrapped: Vec<Trit> = vec![0; subscribers.wrapped_length()];
+        subscribers.wrap(&out, &root, index, &mut wrapped, &mut curl);
+        assert_eq!(
+            unwrap(&wrapped, &alice, &root, index, &mut out[1..], &mut curl),
+            Err(MamError::ArrayOutOfBounds)
+        );
+        assert_eq!(
+            unwrap(&wrapped[..wrapped.len() - 1], &alice, &root, index, &mut out, &mut curl),
+            Err(MamError::ArrayOutOfBounds)
+        );
+
+        let mut huge: Vec<Trit> = vec![0; mam::number_length(usize::max_value()) + ENTRY_LENGTH];
+        mam::write_number(usize::max_value(), &mut Writer::new(&mut huge));
+        assert_eq!(
+            unwrap(&huge, &alice, &root, index, &mut out, &mut curl),
+            Err(MamError::ArrayOutOfBounds)
+        );
+        assert_eq!(
+            unwrap(&[], &alice, &root, index, &mut out, &mut curl),
+            Err(MamError::ArrayOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn subscriber_parses_message() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let message = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let subscriber = trits("SUBSCRIBERKEY");
+        let security = Security::Low;
+        let start: isize = 0;
+        let index = 0;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let root = merkle::create(&seed, start, 1, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root_trits: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&root, &mut root_trits);
+
+        let mut subscribers = Subscribers::new();
+        subscribers.add(&subscriber).unwrap();
+
+        let mut payload: Vec<Trit> =
+            vec![0; min_length(&subscribers, message.len(), 0, index, security)];
+        create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &subscribers,
+            &seed,
+            &message,
+            &root_trits,
+            &[],
+            &root_trits,
+            start,
+            index,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+
+        let mut revoked = payload.clone();
+        let parsed = parse(&mut payload, &subscriber, &root_trits, index, &mut c1).unwrap();
+        assert_eq!(parsed.message(&payload), &message[..]);
+        assert_eq!(
+            parse(&mut revoked, &trits("OTHERKEY"), &root_trits, index, &mut c1),
+            Err(MamError::NotSubscribed)
+        );
+    }
+
+    #[test]
+    fn rejects_unsigned_payloads_forged_by_subscribers() {
+        let subscriber = trits("SUBSCRIBERKEY");
+        let mut subscribers = Subscribers::new();
+        subscribers.add(&subscriber).unwrap();
+        let index = 1;
+        let (root, mut payload) = publish(&subscribers, &trits("GENUINE"), index);
+
+        // A subscriber knows the content key, and keeps the genuine wrapped
+        // key block in front of a payload of their own
+        let mut curl = CpuCurl::<Trit>::default();
+        let mut key: [Trit; keys::KEY_LENGTH] = [0; keys::KEY_LENGTH];
+        let wrapped_end = unwrap(&payload, &subscriber, &root, index, &mut key, &mut curl).unwrap();
+        let message = trits("FORGED");
+        let mut forged: Vec<Trit> = payload[..wrapped_end].to_vec();
+        forged.resize(wrapped_end + mam::unsigned_min_length(message.len(), index), 0);
+        mam::create_unsigned(
+            &message,
+            &key,
+            &root,
+            &root,
+            index,
+            &mut forged[wrapped_end..],
+            &mut curl,
+        );
+        assert_eq!(
+            parse(&mut forged, &subscriber, &root, index, &mut curl),
+            Err(MamError::SecurityTooLow)
+        );
+        assert!(parse(&mut payload, &subscriber, &root, index, &mut curl).is_ok());
+    }
+
+    #[test]
+    fn rejects_tampered_wrapped_keys() {
+        let subscriber = trits("SUBSCRIBERKEY");
+        let mut subscribers = Subscribers::new();
+        subscribers.add(&subscriber).unwrap();
+        let index = 1;
+        let (root, payload) = publish(&subscribers, &trits("GENUINE"), index);
+        let entry_start = mam::number_length(subscribers.len());
+        let mut curl = CpuCurl::<Trit>::default();
+
+        let mut tampered = payload.clone();
+        let pos = entry_start + ID_LENGTH;
+        tampered[pos] = if tampered[pos] == 1 { 0 } else { 1 };
+        assert!(parse(&mut tampered, &subscriber, &root, index, &mut curl).is_err());
+
+        let mut tampered = payload.clone();
+        tampered[entry_start] = if tampered[entry_start] == 1 { 0 } else { 1 };
+        assert_eq!(
+            parse(&mut tampered, &subscriber, &root, index, &mut curl),
+            Err(MamError::NotSubscribed)
+        );
+    }
+
+    #[test]
+    fn rejects_wrapped_keys_of_other_indexes() {
+        let subscriber = trits("SUBSCRIBERKEY");
+        let mut subscribers = Subscribers::new();
+        subscribers.add(&subscriber).unwrap();
+        let (root, first) = publish(&subscribers, &trits("FIRST"), 0);
+        let (_, second) = publish(&subscribers, &trits("SECOND"), 1);
+        let wrapped_end = subscribers.wrapped_length();
+        let mut curl = CpuCurl::<Trit>::default();
+
+        let mut spliced: Vec<Trit> = first[..wrapped_end].to_vec();
+        spliced.extend_from_slice(&second[wrapped_end..]);
+        for index in 0..2 {
+            assert!(parse(&mut spliced.clone(), &subscriber, &root, index, &mut curl).is_err());
+        }
+        assert!(parse(&mut second.clone(), &subscriber, &root, 0, &mut curl).is_err());
+        assert!(parse(&mut second.clone(), &subscriber, &root, 1, &mut curl).is_ok());
+    }
+
+    #[test]
+    fn rejects_payloads_signed_for_other_indexes() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let subscriber = trits("SUBSCRIBERKEY");
+        let message = trits("MISPLACED");
+        let security = Security::Low;
+        let start: isize = 0;
+        let mut subscribers = Subscribers::new();
+        subscribers.add(&subscriber).unwrap();
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let tree = merkle::create(&seed, start, 2, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&tree, &mut root);
+        let branch = merkle::branch(&tree, 1);
+        let siblings_length = merkle::len(&branch) * HASH_LENGTH;
+        let mut siblings: Vec<Trit> = vec![0; siblings_length];
+        merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
+
+        // Signed with the key at index 1, but masked with the content key
+        // of index 0
+        let mut key: [Trit; keys::KEY_LENGTH] = [0; keys::KEY_LENGTH];
+        content_key(&seed, &root, 0, &mut key, &mut c1);
+        let mut payload: Vec<Trit> =
+            vec![0; min_length(&subscribers, message.len(), siblings.len(), 1, security)];
+        let wrapped_end = subscribers.wrap(&key, &root, 0, &mut payload, &mut c1);
+        mam::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &message,
+            &key,
+            &root,
+            &siblings,
+            &root,
+            start,
+            1,
+            security,
+            &mut payload[wrapped_end..],
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+
+        assert_eq!(
+            parse(&mut payload, &subscriber, &root, 0, &mut c1),
+            Err(MamError::IndexMismatch)
+        );
+    }
+}
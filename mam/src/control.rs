@@ -0,0 +1,305 @@
+//! End-of-channel and revocation messages
+//!
+//! An end-of-channel payload is a `MessageType::End` payload whose message
+//! tells why the channel was closed. A revocation is a `MessageType::Revoke`
+//! payload whose message starts with the revoked root, e.g. of a compromised
+//! tree or an announced child channel:
+//!
+//! ```text
+//! [
+//!     Revoked Root,
+//!     Reason,
+//! ]
+//! ```
+//!
+//! Both are signed like any other message, and a `Subscriber` stops following
+//! the channel once it receives either for its current root.
+use alloc::Vec;
+use curl::*;
+use trytes::*;
+use errors::*;
+use security::*;
+use mam::{self, MessageType, Parsed};
+
+/// Minimum length of an end-of-channel payload with a `reason` of
+/// `reason_length` trits
+pub fn end_min_length(
+    reason_length: usize,
+    siblings_length: usize,
+    index: usize,
+    security: Security,
+) -> usize {
+    mam::signed_min_length(
+        MessageType::End,
+        reason_length,
+        siblings_length,
+        index,
+        security,
+    )
+}
+
+/// Creates a signed, encrypted payload closing the channel for the given
+/// `reason`.
+///
+/// Takes the same parameters as `mam::create`.
+///
+/// Returns the length of the payload, or `MamError::NonceSearchFailed` if
+/// no hamming nonce was found
+pub fn create_end<C, CB, H>(
+    seed: &[Trit],
+    reason: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next: &[Trit],
+    start: isize,
+    index: usize,
+    security: Security,
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+{
+    mam::create_signed::<C, CB, H>(
+        MessageType::End,
+        seed,
+        reason,
+        side_key,
+        root,
+        siblings,
+        next,
+        start,
+        index,
+        security,
+        payload,
+        curl,
+        encr_curl,
+        bcurl,
+    )
+}
+
+/// Minimum length of a revocation payload with a `reason` of
+/// `reason_length` trits
+pub fn revocation_min_length(
+    reason_length: usize,
+    siblings_length: usize,
+    index: usize,
+    security: Security,
+) -> usize {
+    mam::signed_min_length(
+        MessageType::Revoke,
+        HASH_LENGTH + reason_length,
+        siblings_length,
+        index,
+        security,
+    )
+}
+
+/// Creates a signed, encrypted payload revoking the `revoked` root for the
+/// given `reason`.
+///
+/// Takes the same parameters as `mam::create`.
+///
+/// Returns the length of the payload, `MamError::ArrayOutOfBounds` if
+/// `revoked` is not `HASH_LENGTH` trits long, or
+/// `MamError::NonceSearchFailed` if no hamming nonce was found
+pub fn create_revocation<C, CB, H>(
+    seed: &[Trit],
+    revoked: &[Trit],
+    reason: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next: &[Trit],
+    start: isize,
+    index: usize,
+    security: Security,
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+{
+    if revoked.len() != HASH_LENGTH {
+        return Err(MamError::ArrayOutOfBounds);
+    }
+    let mut revocation: Vec<Trit> = vec![0; HASH_LENGTH + reason.len()];
+    revocation[..HASH_LENGTH].clone_from_slice(revoked);
+    revocation[HASH_LENGTH..].clone_from_slice(reason);
+    mam::create_signed::<C, CB, H>(
+        MessageType::Revoke,
+        seed,
+        &revocation,
+        side_key,
+        root,
+        siblings,
+        next,
+        start,
+        index,
+        security,
+        payload,
+        curl,
+        encr_curl,
+        bcurl,
+    )
+}
+
+/// The root revoked by a `parsed` payload, if it is a revocation
+pub fn revoked<'a>(parsed: &Parsed, payload: &'a [Trit]) -> Option<&'a [Trit]> {
+    if parsed.kind == MessageType::Revoke {
+        Some(&parsed.message(payload)[..HASH_LENGTH])
+    } else {
+        None
+    }
+}
+
+/// The reason given by a `parsed` end-of-channel or revocation payload
+pub fn reason<'a>(parsed: &Parsed, payload: &'a [Trit]) -> Option<&'a [Trit]> {
+    match parsed.kind {
+        MessageType::End => Some(parsed.message(payload)),
+        MessageType::Revoke => Some(&parsed.message(payload)[HASH_LENGTH..]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+    use merkle;
+
+    fn trits(s: &str) -> Vec<Trit> {
+        s.chars().flat_map(char_to_trits).cloned().collect()
+    }
+
+    fn root_of(seed: &[Trit], security: Security) -> [Trit; HASH_LENGTH] {
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let tree = merkle::create(seed, 0, 1, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&tree, &mut root);
+        root
+    }
+
+    #[test]
+    fn ends_channels() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let side_key = trits("EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD");
+        let reason = trits("GOODBYE");
+        let security = Security::Low;
+        let root = root_of(&seed, security);
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let mut payload: Vec<Trit> = vec![0; end_min_length(reason.len(), 0, 0, security)];
+        create_end::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &reason,
+            &side_key,
+            &root,
+            &[],
+            &root,
+            0,
+            0,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+
+        let parsed = mam::parse(&mut payload, &side_key, &root, &mut c1).unwrap();
+        assert_eq!(parsed.kind, MessageType::End);
+        assert_eq!(super::reason(&parsed, &payload), Some(&reason[..]));
+        assert_eq!(revoked(&parsed, &payload), None);
+    }
+
+    #[test]
+    fn revokes_roots() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let side_key = trits("EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD");
+        let reason = trits("KEYS9LEAKED");
+        let revoked_root: [Trit; HASH_LENGTH] = [-1; HASH_LENGTH];
+        let security = Security::Low;
+        let root = root_of(&seed, security);
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let mut payload: Vec<Trit> =
+            vec![0; revocation_min_length(reason.len(), 0, 0, security)];
+        create_revocation::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &revoked_root,
+            &reason,
+            &side_key,
+            &root,
+            &[],
+            &root,
+            0,
+            0,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+
+        let parsed = mam::parse(&mut payload, &side_key, &root, &mut c1).unwrap();
+        assert_eq!(parsed.kind, MessageType::Revoke);
+        assert_eq!(revoked(&parsed, &payload), Some(&revoked_root[..]));
+        assert_eq!(super::reason(&parsed, &payload), Some(&reason[..]));
+
+        let plain = Parsed {
+            kind: MessageType::Signed,
+            ..parsed
+        };
+        assert_eq!(revoked(&plain, &payload), None);
+        assert_eq!(super::reason(&plain, &payload), None);
+    }
+
+    #[test]
+    fn rejects_short_revoked_roots() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        let security = Security::Low;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let mut payload: Vec<Trit> = vec![0; revocation_min_length(0, 0, 0, security)];
+        assert_eq!(
+            create_revocation::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+                &seed,
+                &root[1..],
+                &[],
+                &seed,
+                &root,
+                &[],
+                &root,
+                0,
+                0,
+                security,
+                &mut payload,
+                &mut c1,
+                &mut c2,
+                &mut bc,
+            ),
+            Err(MamError::ArrayOutOfBounds)
+        );
+    }
+}
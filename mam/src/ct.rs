@@ -0,0 +1,35 @@
+//! Constant-time comparison of trits
+//!
+//! Verification routines compare against values an attacker can choose, so
+//! the time they take must not depend on how many trits matched.
+use core::ptr;
+use trytes::*;
+
+/// Returns whether `a` and `b` are equal, in time depending only on their lengths
+pub fn ct_eq(a: &[Trit], b: &[Trit]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff: Trit = 0;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    unsafe { ptr::read_volatile(&diff) == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::Vec;
+
+    #[test]
+    fn compares_trits() {
+        let a: Vec<Trit> = "SOMEROOT".chars().flat_map(char_to_trits).cloned().collect();
+        let mut b = a.clone();
+        assert!(ct_eq(&a, &b));
+        b[0] = -b[0] + 1;
+        assert!(!ct_eq(&a, &b));
+        assert!(!ct_eq(&a, &a[1..]));
+        assert!(ct_eq(&[], &[]));
+    }
+}
@@ -1,4 +1,5 @@
 use alloc::string::String;
+use pascal::PascalError;
 
 #[derive(Eq, PartialEq, Debug)]
 #[repr(C)]
@@ -10,4 +11,34 @@ pub enum MamError {
     InvalidSignature,
     /// Array was too short
     ArrayOutOfBounds,
+    /// Key was empty, not a whole number of trytes, or contained invalid trits
+    InvalidKey,
+    /// No content key was wrapped for the subscriber
+    NotSubscribed,
+    /// Authentication tag did not match the unmasked payload
+    InvalidTag,
+    /// Payload started with an unknown message type
+    UnknownMessageType,
+    /// Security level was not in `1..=3`
+    InvalidSecurity,
+    /// Message was signed at a lower security level than required
+    SecurityTooLow,
+    /// No hamming nonce was found within the budget, or the search was cancelled
+    NonceSearchFailed,
+    /// Leaf key was generated for a different index or security level
+    KeyMismatch,
+    /// Seed was not 81 trytes of valid trits
+    InvalidSeed,
+    /// Channel was closed or its root revoked
+    ChannelClosed,
+    /// Payload was signed for a different index than the one expected
+    IndexMismatch,
+}
+
+/// Numbers that fail to decode are numbers running past the end of the
+/// payload, or malformed ones that cannot be told apart from those
+impl From<PascalError> for MamError {
+    fn from(_: PascalError) -> Self {
+        MamError::ArrayOutOfBounds
+    }
 }
@@ -0,0 +1,199 @@
+//! Announcements of child channels forked off a channel
+//!
+//! A fork announcement is a `MessageType::Fork` payload signed by the parent
+//! channel, whose message starts with the root of the child channel, so that
+//! subscribers of the parent can trust the child to be run by the same
+//! publisher:
+//!
+//! ```text
+//! [
+//!     Child Root,
+//!     Message,
+//! ]
+//! ```
+use alloc::Vec;
+use curl::*;
+use trytes::*;
+use errors::*;
+use security::*;
+use mam::{self, MessageType, Parsed};
+
+/// Minimum length of a payload announcing a child channel with a `message`
+/// of `message_length` trits
+pub fn min_length(
+    message_length: usize,
+    siblings_length: usize,
+    index: usize,
+    security: Security,
+) -> usize {
+    mam::signed_min_length(
+        MessageType::Fork,
+        HASH_LENGTH + message_length,
+        siblings_length,
+        index,
+        security,
+    )
+}
+
+/// Creates a signed, encrypted payload announcing the `child_root` of a new
+/// channel, along with a `message`, e.g. what the child channel is for.
+///
+/// Takes the same parameters as `mam::create`.
+///
+/// Returns the length of the payload, `MamError::ArrayOutOfBounds` if
+/// `child_root` is not `HASH_LENGTH` trits long, or
+/// `MamError::NonceSearchFailed` if no hamming nonce was found
+pub fn create<C, CB, H>(
+    seed: &[Trit],
+    child_root: &[Trit],
+    message: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next: &[Trit],
+    start: isize,
+    index: usize,
+    security: Security,
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+{
+    if child_root.len() != HASH_LENGTH {
+        return Err(MamError::ArrayOutOfBounds);
+    }
+    let mut announcement: Vec<Trit> = vec![0; HASH_LENGTH + message.len()];
+    announcement[..HASH_LENGTH].clone_from_slice(child_root);
+    announcement[HASH_LENGTH..].clone_from_slice(message);
+    mam::create_signed::<C, CB, H>(
+        MessageType::Fork,
+        seed,
+        &announcement,
+        side_key,
+        root,
+        siblings,
+        next,
+        start,
+        index,
+        security,
+        payload,
+        curl,
+        encr_curl,
+        bcurl,
+    )
+}
+
+/// The root of the child channel announced by a `parsed` payload, if it is
+/// a fork announcement
+pub fn child<'a>(parsed: &Parsed, payload: &'a [Trit]) -> Option<&'a [Trit]> {
+    if parsed.kind == MessageType::Fork {
+        Some(&parsed.message(payload)[..HASH_LENGTH])
+    } else {
+        None
+    }
+}
+
+/// The message accompanying a fork announcement
+pub fn message<'a>(parsed: &Parsed, payload: &'a [Trit]) -> Option<&'a [Trit]> {
+    if parsed.kind == MessageType::Fork {
+        Some(&parsed.message(payload)[HASH_LENGTH..])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+    use merkle;
+
+    fn trits(s: &str) -> Vec<Trit> {
+        s.chars().flat_map(char_to_trits).cloned().collect()
+    }
+
+    #[test]
+    fn announces_child_roots() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let side_key = trits("EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD");
+        let message = trits("CUSTOMER9ONE");
+        let child_root: [Trit; HASH_LENGTH] = [1; HASH_LENGTH];
+        let security = Security::Low;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let tree = merkle::create(&seed, 0, 1, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&tree, &mut root);
+
+        let mut payload: Vec<Trit> = vec![0; min_length(message.len(), 0, 0, security)];
+        create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &child_root,
+            &message,
+            &side_key,
+            &root,
+            &[],
+            &root,
+            0,
+            0,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+
+        let parsed = mam::parse(&mut payload, &side_key, &root, &mut c1).unwrap();
+        assert_eq!(parsed.kind, MessageType::Fork);
+        assert_eq!(child(&parsed, &payload), Some(&child_root[..]));
+        assert_eq!(super::message(&parsed, &payload), Some(&message[..]));
+
+        let plain = Parsed {
+            kind: MessageType::Signed,
+            ..parsed
+        };
+        assert_eq!(child(&plain, &payload), None);
+        assert_eq!(super::message(&plain, &payload), None);
+    }
+
+    #[test]
+    fn rejects_short_child_roots() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        let security = Security::Low;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let mut payload: Vec<Trit> = vec![0; min_length(0, 0, 0, security)];
+        assert_eq!(
+            create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+                &seed,
+                &root[1..],
+                &[],
+                &seed,
+                &root,
+                &[],
+                &root,
+                0,
+                0,
+                security,
+                &mut payload,
+                &mut c1,
+                &mut c2,
+                &mut bc,
+            ),
+            Err(MamError::ArrayOutOfBounds)
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use alloc::Vec;
+use alloc::string::String;
+use curl::*;
+use trytes::*;
+use errors::*;
+use pascal::{self, Writer};
+use wipe::*;
+
+/// Length of a derived side key
+pub const KEY_LENGTH: usize = HASH_LENGTH;
+
+/// Number of trits used to represent a single byte of a passphrase
+const TRITS_PER_BYTE: usize = 2 * TRITS_PER_TRYTE;
+
+fn byte_to_trits(byte: u8, out: &mut [Trit]) {
+    let mut value = byte as isize;
+    for t in out.iter_mut() {
+        let r = ((value + 1) % RADIX as isize) - 1;
+        *t = r as Trit;
+        value = (value - r) / RADIX as isize;
+    }
+}
+
+pub(crate) fn passphrase_to_trits(passphrase: &str) -> Vec<Trit> {
+    let mut trits: Vec<Trit> = vec![0; passphrase.len() * TRITS_PER_BYTE];
+    for (byte, out) in passphrase.bytes().zip(trits.chunks_mut(TRITS_PER_BYTE)) {
+        byte_to_trits(byte, out);
+    }
+    trits
+}
+
+/// Absorbs the length of `trits` ahead of them, so that consecutive inputs
+/// cannot be shifted into one another
+fn absorb_framed<C: Curl<Trit>>(trits: &[Trit], curl: &mut C) {
+    let mut length: Vec<Trit> = vec![0; pascal::encoded_length_unsigned(trits.len() as u64)];
+    Writer::new(&mut length).write_unsigned(trits.len() as u64);
+    curl.absorb(&length);
+    curl.absorb(trits);
+}
+
+/// Derives a side key from a UTF-8 `passphrase`,
+///
+/// * a `salt`, which is absorbed before the passphrase, each prefixed with
+///   its length,
+/// * the number of `iterations` the sponge is squeezed and re-absorbed,
+///   of which at least one is always performed,
+/// * a `curl` instance used as the hash function, e.g. Curl or Kerl
+///
+/// Writes the derived key to `out`
+pub fn from_passphrase<C: Curl<Trit>>(
+    passphrase: &str,
+    salt: &[Trit],
+    iterations: usize,
+    out: &mut [Trit],
+    curl: &mut C,
+) {
+    let mut trits = passphrase_to_trits(passphrase);
+    absorb_framed(salt, curl);
+    absorb_framed(&trits, curl);
+    wipe(&mut trits);
+    curl.squeeze(out);
+    for _ in 1..iterations {
+        curl.absorb(out);
+        curl.squeeze(out);
+    }
+    wipe_curl(curl);
+}
+
+/// Derives a side key from a raw shared `secret`, writing it to `out`
+pub fn from_secret<C: Curl<Trit>>(secret: &[Trit], out: &mut [Trit], curl: &mut C) {
+    curl.absorb(secret);
+    curl.squeeze(out);
+    curl.reset();
+}
+
+/// Checks that `key` is a non-empty, whole number of trytes made of valid trits
+pub fn validate(key: &[Trit]) -> Result<(), MamError> {
+    if key.is_empty() || key.len() % TRITS_PER_TRYTE != 0 ||
+        key.iter().any(|&t| t < -1 || t > 1)
+    {
+        Err(MamError::InvalidKey)
+    } else {
+        Ok(())
+    }
+}
+
+/// Formats a valid `key` as a tryte string
+pub fn to_trytes(key: &[Trit]) -> Result<String, MamError> {
+    validate(key)?;
+    trits_to_string(key).ok_or(MamError::InvalidKey)
+}
+
+/// Parses a side key from a tryte string
+pub fn from_trytes(key: &str) -> Result<Vec<Trit>, MamError> {
+    if key.is_empty() || !key.chars().all(|c| c == '9' || (c >= 'A' && c <= 'Z')) {
+        return Err(MamError::InvalidKey);
+    }
+    Ok(key.chars().flat_map(char_to_trits).cloned().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+
+    #[test]
+    fn passphrase_is_deterministic() {
+        let salt: Vec<Trit> = "MYCHANNELSALT".chars().flat_map(char_to_trits).cloned().collect();
+        let mut curl = CpuCurl::<Trit>::default();
+        let mut k1 = [0; KEY_LENGTH];
+        let mut k2 = [0; KEY_LENGTH];
+        from_passphrase("correct horse battery staple", &salt, 10, &mut k1, &mut curl);
+        from_passphrase("correct horse battery staple", &salt, 10, &mut k2, &mut curl);
+        assert_eq!(&k1[..], &k2[..]);
+        assert!(validate(&k1).is_ok());
+
+        from_passphrase("correct horse battery staple", &salt, 11, &mut k2, &mut curl);
+        assert!(&k1[..] != &k2[..]);
+        from_passphrase("correct horse battery staple", &salt[3..], 10, &mut k2, &mut curl);
+        assert!(&k1[..] != &k2[..]);
+        from_passphrase("correct horse battery stapler", &salt, 10, &mut k2, &mut curl);
+        assert!(&k1[..] != &k2[..]);
+    }
+
+    #[test]
+    fn salt_and_passphrase_are_framed() {
+        let salt: Vec<Trit> = "MYCHANNELSALT".chars().flat_map(char_to_trits).cloned().collect();
+        let mut longer_salt = salt.clone();
+        longer_salt.extend_from_slice(&passphrase_to_trits("correct"));
+        let mut curl = CpuCurl::<Trit>::default();
+        let mut k1 = [0; KEY_LENGTH];
+        let mut k2 = [0; KEY_LENGTH];
+        from_passphrase("correct horse", &salt, 1, &mut k1, &mut curl);
+        from_passphrase(" horse", &longer_salt, 1, &mut k2, &mut curl);
+        assert!(&k1[..] != &k2[..]);
+    }
+
+    #[test]
+    fn secret_round_trips_as_trytes() {
+        let secret: Vec<Trit> = "SHAREDSECRET".chars().flat_map(char_to_trits).cloned().collect();
+        let mut curl = CpuCurl::<Trit>::default();
+        let mut key = [0; KEY_LENGTH];
+        from_secret(&secret, &mut key, &mut curl);
+        let trytes = to_trytes(&key).unwrap();
+        assert_eq!(trytes.len(), KEY_LENGTH / TRITS_PER_TRYTE);
+        assert_eq!(from_trytes(&trytes).unwrap(), key.to_vec());
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        assert_eq!(validate(&[]), Err(MamError::InvalidKey));
+        assert_eq!(validate(&[1, 0]), Err(MamError::InvalidKey));
+        assert_eq!(validate(&[1, 0, 2]), Err(MamError::InvalidKey));
+        assert_eq!(from_trytes("ABC DEF"), Err(MamError::InvalidKey));
+        assert_eq!(from_trytes("abc"), Err(MamError::InvalidKey));
+    }
+}
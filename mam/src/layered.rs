@@ -0,0 +1,506 @@
+//! Layered channels of a top-level tree certifying lazily generated subtrees
+//!
+//! The channel root is the root of a top-level tree. Each of its leaves signs
+//! the root of one subtree, whose seed is derived with `Seed::subtree`, so a
+//! subtree only needs to be generated once the previous one is used up. The
+//! signature is made once per subtree with `certify`, and the resulting
+//! certificate is appended to every message of that subtree:
+//!
+//! ```text
+//! [
+//!     Top Index,
+//!     Nonce,
+//!     Signature,
+//!     Siblings Count,
+//!     Siblings,
+//! ]
+//! ```
+//!
+//! Numbers are encoded like in the rest of the payload, see `mam::Version`.
+//! The nonce is chosen so that `H(Subtree Root + Nonce)` can be signed at the
+//! message's security level, at which the top-level tree is signed as well.
+//! `parse` resolves a `MessageType::Layered` payload to its subtree root, then
+//! resolves the certificate of that subtree root to the channel root.
+use core::cmp;
+use curl::*;
+use sign::iss;
+use merkle;
+use trytes::*;
+use mask::*;
+use errors::*;
+use ct::*;
+use security::*;
+use nonce::*;
+use leaf::*;
+use mam::{self, MessageType, Version};
+use pascal::{ReaderMut, Writer};
+
+/// Length of the certificate signed by the leaf at `top_index`, with
+/// `siblings_length` trits of top-level siblings
+pub fn certificate_length(top_index: usize, siblings_length: usize, security: Security) -> usize {
+    mam::number_length(top_index) + NONCE_LENGTH + security.signature_length() +
+        mam::number_length(siblings_length / HASH_LENGTH) + siblings_length
+}
+
+/// Certifies the `sub_root` of a subtree, writing the certificate to `out`,
+///
+/// * the `seed` of the top-level tree,
+/// * the `siblings` of the leaf at `top_index` in the top-level tree,
+/// * the `start` index of the top-level tree,
+/// * the `top_index` relative to the top-level tree of the key being used,
+/// * the `security` parameter, which all messages of the subtree must use,
+/// * a `curl` instance of Trit Curl for use in finding the hamming nonce and signing,
+/// * a `bcurl` instance of binary coded trits Curl for use in finding the hamming nonce
+///
+/// Returns the length of the certificate, or `MamError::NonceSearchFailed` if
+/// no hamming nonce was found
+pub fn certify<C, CB, H>(
+    seed: &[Trit],
+    sub_root: &[Trit],
+    siblings: &[Trit],
+    start: isize,
+    top_index: usize,
+    security: Security,
+    out: &mut [Trit],
+    curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+{
+    let length = certificate_length(top_index, siblings.len(), security);
+    assert!(
+        out.len() >= length,
+        "should be: {}, is {}",
+        length,
+        out.len()
+    );
+    curl.absorb(sub_root);
+    let found = H::search(security.level() as u8, 0, NONCE_LENGTH, curl, bcurl).is_some();
+    let mut nonce: [Trit; NONCE_LENGTH] = [0; NONCE_LENGTH];
+    nonce.clone_from_slice(&curl.rate()[..NONCE_LENGTH]);
+    bcurl.reset();
+    curl.reset();
+
+    let mut hash: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+    curl.absorb(sub_root);
+    curl.absorb(&nonce);
+    hash.clone_from_slice(curl.rate());
+    curl.reset();
+    if !found || iss::checksum_security(&hash) != security.level() {
+        return Err(MamError::NonceSearchFailed);
+    }
+
+    let mut writer = Writer::new(out);
+    mam::write_number(top_index, &mut writer);
+    writer.write_trits(&nonce);
+    LeafKey::new(seed, start, top_index, security, curl).sign(
+        &hash,
+        writer.reserve(security.signature_length()),
+        curl,
+    );
+    mam::write_number(siblings.len() / HASH_LENGTH, &mut writer);
+    writer.write_trits(siblings);
+    Ok(writer.position())
+}
+
+/// Minimum length of a payload made by `create` with a certificate of
+/// `certificate_length` trits
+pub fn min_length(
+    certificate_length: usize,
+    message_length: usize,
+    siblings_length: usize,
+    index: usize,
+    security: Security,
+) -> usize {
+    mam::signed_min_length(
+        MessageType::Layered,
+        message_length,
+        siblings_length,
+        index,
+        security,
+    ) + certificate_length
+}
+
+/// Creates a signed, encrypted payload from a `message` like `mam::create`,
+/// with the `seed`, `siblings`, `start` and `index` of the subtree, and the
+/// `certificate` of the subtree's root made by `certify`. The `root` is the
+/// root of the top-level tree.
+///
+/// Returns the length of the signed, encrypted `payload`,
+/// `MamError::ArrayOutOfBounds` if the `certificate` is not laid out like one
+/// made by `certify` at the `security` level, or
+/// `MamError::NonceSearchFailed` if no hamming nonce was found
+pub fn create<C, CB, H>(
+    seed: &[Trit],
+    certificate: &[Trit],
+    message: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next: &[Trit],
+    start: isize,
+    index: usize,
+    security: Security,
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+{
+    signature_end(certificate, security)?;
+    let key = LeafKey::new(seed, start, index, security, curl);
+    mam::finalize_signed::<C, CB, H, _>(
+        MessageType::Layered,
+        index,
+        security,
+        |hash, out, curl| {
+            key.sign(hash, out, curl);
+            Ok(())
+        },
+        certificate,
+        message,
+        side_key,
+        root,
+        siblings,
+        next,
+        payload,
+        curl,
+        encr_curl,
+        bcurl,
+    )
+}
+
+/// Checks that `certificate` holds a top index, nonce, signature at the
+/// `security` level and exactly as many siblings as it counts, returning
+/// the end of the signature
+fn signature_end(certificate: &[Trit], security: Security) -> Result<usize, MamError> {
+    let (_, index_length) = Version::Compact.decode(certificate)?;
+    let signature_end = index_length + NONCE_LENGTH + security.signature_length();
+    if signature_end > certificate.len() {
+        return Err(MamError::ArrayOutOfBounds);
+    }
+    let (siblings_count, count_length) = Version::Compact.decode(&certificate[signature_end..])?;
+    let siblings_length = certificate.len() - signature_end - count_length;
+    if siblings_length % HASH_LENGTH != 0 || siblings_length / HASH_LENGTH != siblings_count {
+        return Err(MamError::ArrayOutOfBounds);
+    }
+    Ok(signature_end)
+}
+
+/// Masks a `certificate` made by `certify`, splitting it where
+/// `unmask_certificate` has to read the siblings count
+///
+/// Returns `MamError::ArrayOutOfBounds` if the `certificate` is malformed
+pub(crate) fn mask_certificate<C>(
+    certificate: &mut [Trit],
+    security: Security,
+    curl: &mut C,
+) -> Result<(), MamError>
+where
+    C: Curl<Trit>,
+{
+    let signature_end = signature_end(certificate, security)?;
+    let (signed, siblings) = certificate.split_at_mut(signature_end);
+    mask_slice(signed, curl);
+    mask_slice(siblings, curl);
+    Ok(())
+}
+
+/// Reads a number from the masked start of `payload`
+fn peek_number<C>(payload: &[Trit], version: Version, curl: &C) -> Result<(usize, usize), MamError>
+where
+    C: Curl<Trit>,
+{
+    let mut encoded: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+    let len = cmp::min(HASH_LENGTH, payload.len());
+    peek_slice(payload, &mut encoded[..len], curl);
+    version.decode(&encoded[..len])
+}
+
+/// A certificate unmasked by `unmask_certificate`
+pub(crate) struct Certificate<'a> {
+    top_index: usize,
+    nonce: &'a [Trit],
+    signature: &'a mut [Trit],
+    siblings: &'a [Trit],
+}
+
+/// Reads and unmasks the certificate following the siblings of a payload
+pub(crate) fn unmask_certificate<'a, C>(
+    reader: &mut ReaderMut<'a>,
+    version: Version,
+    security: Security,
+    curl: &mut C,
+) -> Result<Certificate<'a>, MamError>
+where
+    C: Curl<Trit>,
+{
+    let (top_index, index_length) = peek_number(reader.rest(), version, curl)?;
+    let signed = reader.read_trits(
+        index_length + NONCE_LENGTH + security.signature_length(),
+    )?;
+    unmask_slice(signed, curl);
+    let (siblings_count, count_length) = peek_number(reader.rest(), version, curl)?;
+    if siblings_count > reader.remaining() / HASH_LENGTH {
+        return Err(MamError::ArrayOutOfBounds);
+    }
+    let siblings = reader.read_trits(count_length + siblings_count * HASH_LENGTH)?;
+    unmask_slice(siblings, curl);
+    let (nonce, signature) = signed[index_length..].split_at_mut(NONCE_LENGTH);
+    Ok(Certificate {
+        top_index: top_index,
+        nonce: nonce,
+        signature: signature,
+        siblings: &siblings[count_length..],
+    })
+}
+
+/// Checks that the unmasked `certificate` of `sub_root` resolves to `root`
+pub(crate) fn verify_certificate<C>(
+    certificate: Certificate,
+    sub_root: &[Trit],
+    security: Security,
+    root: &[Trit],
+    curl: &mut C,
+) -> bool
+where
+    C: Curl<Trit>,
+{
+    let mut hash: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+    curl.absorb(sub_root);
+    curl.absorb(certificate.nonce);
+    hash.clone_from_slice(curl.rate());
+    curl.reset();
+    if iss::checksum_security(&hash) != security.level() {
+        return false;
+    }
+    iss::digest_bundle_signature(&hash, certificate.signature, curl);
+    hash.clone_from_slice(curl.rate());
+    curl.reset();
+    curl.absorb(&hash);
+    if !certificate.siblings.is_empty() {
+        hash.clone_from_slice(curl.rate());
+        curl.reset();
+        merkle::root(&hash, certificate.siblings, certificate.top_index, curl);
+    }
+    let valid = ct_eq(curl.rate(), root);
+    curl.reset();
+    valid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+    use alloc::Vec;
+    use seed::*;
+
+    fn tree_root(
+        seed: &[Trit],
+        count: usize,
+        index: usize,
+        security: Security,
+        root: &mut [Trit],
+        curl: &mut CpuCurl<Trit>,
+    ) -> Vec<Trit> {
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let tree = merkle::create(seed, 0, count, security.level(), curl, &mut c2, &mut c3);
+        merkle::slice(&tree, root);
+        let branch = merkle::branch(&tree, index);
+        let siblings_length = merkle::len(&branch) * HASH_LENGTH;
+        let mut siblings: Vec<Trit> = vec![0; siblings_length];
+        if siblings_length > 0 {
+            merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
+        }
+        siblings
+    }
+
+    /// Certifies the subtree at `top_index` of a top-level tree of two
+    /// leaves, returning the subtree's seed, the siblings of its leaf at
+    /// `index` and the certificate
+    fn certified_subtree(
+        seed: &Seed,
+        top_index: usize,
+        index: usize,
+        security: Security,
+        root: &mut [Trit],
+    ) -> (Seed, Vec<Trit>, Vec<Trit>) {
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let top_siblings = tree_root(seed, 2, top_index, security, root, &mut c1);
+        let sub_seed = seed.subtree(top_index, &mut c1);
+        let mut sub_root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        let siblings = tree_root(&sub_seed, 2, index, security, &mut sub_root, &mut c1);
+
+        let mut certificate: Vec<Trit> =
+            vec![0; certificate_length(top_index, top_siblings.len(), security)];
+        certify::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            seed,
+            &sub_root,
+            &top_siblings,
+            0,
+            top_index,
+            security,
+            &mut certificate,
+            &mut c1,
+            &mut bc,
+        ).unwrap();
+        (sub_seed, siblings, certificate)
+    }
+
+    fn publish(
+        sub_seed: &Seed,
+        certificate: &[Trit],
+        siblings: &[Trit],
+        message: &[Trit],
+        side_key: &[Trit],
+        root: &[Trit],
+        index: usize,
+        security: Security,
+    ) -> Result<Vec<Trit>, MamError> {
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+        let next: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+
+        let mut payload: Vec<Trit> = vec![
+            0;
+            min_length(
+                certificate.len(),
+                message.len(),
+                siblings.len(),
+                index,
+                security,
+            )
+        ];
+        create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            sub_seed,
+            certificate,
+            message,
+            side_key,
+            root,
+            siblings,
+            &next,
+            0,
+            index,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).map(|_| payload)
+    }
+
+    #[test]
+    fn parses_certified_subtree_messages() {
+        let seed = Seed::from_trytes(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9",
+        ).unwrap();
+        let message: Vec<Trit> = "LAYEREDCHANNELMESSAGE".chars().flat_map(char_to_trits).cloned().collect();
+        let side_key: Vec<Trit> = "LAYEREDSIDEKEY".chars().flat_map(char_to_trits).cloned().collect();
+        let security = Security::Low;
+        let index = 1;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        let subtrees: Vec<(Seed, Vec<Trit>, Vec<Trit>)> = (0..2)
+            .map(|top_index| certified_subtree(&seed, top_index, index, security, &mut root))
+            .collect();
+
+        for &(ref sub_seed, ref siblings, ref certificate) in &subtrees {
+            let mut payload = publish(
+                sub_seed,
+                certificate,
+                siblings,
+                &message,
+                &side_key,
+                &root,
+                index,
+                security,
+            ).unwrap();
+            let parsed = mam::parse(&mut payload, &side_key, &root, &mut c1).unwrap();
+            assert_eq!(parsed.kind, MessageType::Layered);
+            assert_eq!(parsed.message(&payload), &message[..]);
+        }
+
+        // The certificate of one subtree must not verify messages of the other
+        let (ref sub_seed, ref siblings, _) = subtrees[1];
+        let mut payload = publish(
+            sub_seed,
+            &subtrees[0].2,
+            siblings,
+            &message,
+            &side_key,
+            &root,
+            index,
+            security,
+        ).unwrap();
+        assert_eq!(
+            mam::parse(&mut payload, &side_key, &root, &mut c1),
+            Err(MamError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_certificates() {
+        let seed = Seed::from_trytes(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9",
+        ).unwrap();
+        let message: Vec<Trit> = "LAYEREDCHANNELMESSAGE".chars().flat_map(char_to_trits).cloned().collect();
+        let side_key: Vec<Trit> = "LAYEREDSIDEKEY".chars().flat_map(char_to_trits).cloned().collect();
+        let security = Security::Low;
+        let index = 0;
+
+        let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        let (sub_seed, siblings, certificate) =
+            certified_subtree(&seed, 1, index, security, &mut root);
+
+        let mut long_certificate = certificate.clone();
+        long_certificate.extend_from_slice(&root);
+        let malformed: [&[Trit]; 4] = [
+            &[],
+            &certificate[..certificate.len() - 1],
+            &certificate[..certificate.len() - HASH_LENGTH],
+            &long_certificate,
+        ];
+        for certificate in malformed.iter() {
+            assert_eq!(
+                publish(
+                    &sub_seed,
+                    certificate,
+                    &siblings,
+                    &message,
+                    &side_key,
+                    &root,
+                    index,
+                    security,
+                ),
+                Err(MamError::ArrayOutOfBounds)
+            );
+        }
+
+        // a certificate for a higher security level has too long a signature
+        assert_eq!(
+            publish(
+                &sub_seed,
+                &certificate,
+                &siblings,
+                &message,
+                &side_key,
+                &root,
+                index,
+                Security::Medium,
+            ),
+            Err(MamError::ArrayOutOfBounds)
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! One-time leaf keys, generated ahead of publishing
+//!
+//! Generating the private key of a merkle leaf is the most expensive step of
+//! `create`. A publisher can generate the keys of upcoming messages with
+//! `LeafKey::new` during idle time, and publish with `finalize` later.
+//!
+//! The private key is wiped from memory once it is used up or dropped.
+use alloc::Vec;
+use curl::*;
+use sign::iss;
+use trytes::*;
+use security::*;
+use wipe::*;
+
+/// The one-time private key of the merkle leaf at `start + index` of a seed
+pub struct LeafKey {
+    index: usize,
+    security: Security,
+    key: Vec<Trit>,
+}
+
+impl LeafKey {
+    /// Generates the private key used to sign the message at `index` of the
+    /// tree beginning at `start` of the `seed`
+    pub fn new<C: Curl<Trit>>(
+        seed: &[Trit],
+        start: isize,
+        index: usize,
+        security: Security,
+        curl: &mut C,
+    ) -> Self {
+        let mut key: Vec<Trit> = vec![0; security.signature_length()];
+        iss::subseed(seed, start + index as isize, &mut key[..HASH_LENGTH], curl);
+        wipe_curl(curl);
+        iss::key(&mut key, security.level(), curl);
+        wipe_curl(curl);
+        LeafKey {
+            index: index,
+            security: security,
+            key: key,
+        }
+    }
+
+    /// Index of the leaf relative to the start of its tree
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn security(&self) -> Security {
+        self.security
+    }
+
+    /// Writes the address of the leaf to `out`, without using up the key
+    pub(crate) fn address<C: Curl<Trit>>(&self, out: &mut [Trit], curl: &mut C, digest_curl: &mut C) {
+        let mut digests: Vec<Trit> = vec![0; self.security.level() * HASH_LENGTH];
+        iss::digest_key(&self.key, &mut digests, curl, digest_curl);
+        wipe_curl(curl);
+        wipe_curl(digest_curl);
+        iss::address(&mut digests, curl);
+        curl.reset();
+        out.clone_from_slice(&digests[..HASH_LENGTH]);
+    }
+
+    /// Signs `hash`, writing the signature to `out`. The key is used up.
+    pub(crate) fn sign<C: Curl<Trit>>(self, hash: &[Trit], out: &mut [Trit], curl: &mut C) {
+        out.clone_from_slice(&self.key);
+        iss::signature(hash, out, curl);
+        wipe_curl(curl);
+    }
+}
+
+impl Drop for LeafKey {
+    fn drop(&mut self) {
+        wipe(&mut self.key);
+    }
+}
@@ -16,9 +16,33 @@
 //!
 //! ```text
 //! [
+//!     Version Marker,
+//!     Encoded Message Type,
 //!     Encoded Index,
 //!     Encoded Message Length,
 //!     encrypted[
+//!         Next Root,
+//!         Message,
+//!         Nonce,
+//!         Signature,
+//!         Encoded Number of Siblings,
+//!         Siblings
+//!     ],
+//!     Tag
+//! ]
+//! ```
+//!
+//! Numbers are encoded with the compact unsigned pascal encoding.
+//!
+//! Payloads made before message types and tags were added, which lack the
+//! version marker, are still parsed as signed messages:
+//!
+//! ```text
+//! [
+//!     Encoded Index,
+//!     Encoded Message Length,
+//!     encrypted[
+//!         Next Root,
 //!         Message,
 //!         Nonce,
 //!         Signature,
@@ -28,6 +52,11 @@
 //! ]
 //! ```
 //!
+//! Their numbers use the signed pascal encoding.
+//!
+//! The tag is squeezed from the encryption sponge after masking, and lets a
+//! subscriber reject tampered payloads before checking the signature.
+//!
 //! # Example
 //!
 //! ```
@@ -40,7 +69,7 @@
 //!     let mut c1 = CpuCurl::<Trit>::default();
 //!     let mut c2 = CpuCurl::<Trit>::default();
 //!     let mut bc = CpuCurl::<BCTrit>::default();
-//!     let security = 1;
+//!     let security = mam::Security::Low;
 //!     let start = 1;
 //!     let index = 1;
 //!     // Some seed for signatures
@@ -62,7 +91,7 @@
 //!     let siblings_trits: Vec<Trit> = siblings.chars().flat_map(char_to_trits).cloned().collect();
 //!
 //!     // Create the payload
-//!     let mut masked_payload: Vec<Trit> = vec![0; mam::min_length(message_trits.len(), siblings_trits.len(), index, security as usize)];
+//!     let mut masked_payload: Vec<Trit> = vec![0; mam::min_length(message_trits.len(), siblings_trits.len(), index, security)];
 //!     mam::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
 //!         &seed_trits,
 //!         &message_trits,
@@ -77,11 +106,11 @@
 //!         &mut c1,
 //!         &mut c2,
 //!         &mut bc,
-//!     );
+//!     ).unwrap();
 //!
 //!     // We'll test that it matches the original message
 //!     match mam::parse(&mut masked_payload, &side_key_trits, &root_trits, &mut c1) {
-//!         Ok((s, end)) => assert_eq!(trits_to_string(&masked_payload[s + HASH_LENGTH..end]), trits_to_string(&message_trits)),
+//!         Ok(parsed) => assert_eq!(trits_to_string(parsed.message(&masked_payload)), trits_to_string(&message_trits)),
 //!         Err(e) => {
 //!             match e {
 //!                 mam::MamError::InvalidSignature => panic!("Invalid Signature"),
@@ -100,6 +129,8 @@
 
 #[macro_use]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 extern crate iota_trytes as trytes;
 extern crate iota_tmath as tmath;
@@ -108,15 +139,40 @@ extern crate iota_sign as sign;
 extern crate iota_merkle as merkle;
 extern crate iota_pascal as pascal;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "std"))]
 extern crate iota_curl_cpu as curl_cpu;
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
 
 pub mod errors;
+pub mod ct;
 pub mod mask;
+pub mod sliced;
+pub mod security;
+pub mod nonce;
+#[cfg(feature = "std")]
+pub mod threaded;
+pub mod keys;
+pub mod leaf;
+pub mod signer;
+pub mod seed;
+pub mod wipe;
+pub mod access;
+pub mod packet;
+pub mod layered;
+pub mod fork;
+pub mod control;
+pub mod subscriber;
 mod mam;
 
 pub use mam::*;
 pub use errors::*;
+pub use security::*;
+pub use leaf::LeafKey;
+pub use signer::{Signer, SeedSigner};
+pub use seed::Seed;
+pub use subscriber::{Subscriber, State};
 /*
  * Address: H ( H ( CKey + Root + Index ) )
  * Tag: Any
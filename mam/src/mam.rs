@@ -1,12 +1,93 @@
+use core::cmp;
 use curl::*;
 use sign::iss;
 use merkle;
 use trytes::*;
 use mask::*;
 use errors::*;
-use pascal;
+use ct::*;
+use security::*;
+use nonce::*;
+use leaf::*;
+use signer::*;
+use layered;
+use pascal::{self, Reader, ReaderMut, Writer};
 
-const MESSAGE_NONCE_LENGTH: usize = HASH_LENGTH / 3;
+/// The kind of message carried by a payload, encoded at its very start
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum MessageType {
+    /// Signed with a one-time key of the channel's merkle tree
+    Signed,
+    /// Authenticated only by the tag, i.e. by anyone holding the side key
+    Unsigned,
+    /// Signed, carrying several length-prefixed messages, see `packet`
+    Packet,
+    /// Signed with a subtree key certified by a top-level tree, see `layered`
+    Layered,
+    /// Signed, announcing the root of a child channel, see `fork`
+    Fork,
+    /// Signed, closing the channel, see `control`
+    End,
+    /// Signed, revoking a root, see `control`
+    Revoke,
+}
+
+impl MessageType {
+    fn code(self) -> usize {
+        match self {
+            MessageType::Signed => 0,
+            MessageType::Unsigned => 1,
+            MessageType::Packet => 2,
+            MessageType::Layered => 3,
+            MessageType::Fork => 4,
+            MessageType::End => 5,
+            MessageType::Revoke => 6,
+        }
+    }
+
+    fn from_code(code: usize) -> Result<MessageType, MamError> {
+        match code {
+            0 => Ok(MessageType::Signed),
+            1 => Ok(MessageType::Unsigned),
+            2 => Ok(MessageType::Packet),
+            3 => Ok(MessageType::Layered),
+            4 => Ok(MessageType::Fork),
+            5 => Ok(MessageType::End),
+            6 => Ok(MessageType::Revoke),
+            _ => Err(MamError::UnknownMessageType),
+        }
+    }
+
+    /// Whether the message starts with a root, as fork announcements and
+    /// revocations do
+    fn carries_root(self) -> bool {
+        self == MessageType::Fork || self == MessageType::Revoke
+    }
+}
+
+/// The location of the contents of a successfully parsed payload
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Parsed {
+    pub kind: MessageType,
+    /// Index of the signing key relative to the tree, as given in the header
+    pub index: usize,
+    /// Security level the message was signed at, if it was signed
+    pub security: Option<Security>,
+    /// Start of the next root, which is followed by the message
+    pub start: usize,
+    /// End of the message
+    pub end: usize,
+}
+
+impl Parsed {
+    pub fn next<'a>(&self, payload: &'a [Trit]) -> &'a [Trit] {
+        &payload[self.start..self.start + HASH_LENGTH]
+    }
+
+    pub fn message<'a>(&self, payload: &'a [Trit]) -> &'a [Trit] {
+        &payload[self.start + HASH_LENGTH..self.end]
+    }
+}
 
 pub fn id<C: Curl<Trit>>(side_key: &[Trit], root: &[Trit], out: &mut [Trit], c: &mut C) {
     c.absorb(side_key);
@@ -15,16 +96,124 @@ pub fn id<C: Curl<Trit>>(side_key: &[Trit], root: &[Trit], out: &mut [Trit], c:
     c.reset();
 }
 
+/// Start of the payloads made by `create`. Payloads made before message
+/// types and tags were added start with their index, encoded with
+/// `pascal::encode`. A single positive tryte followed by a negative encoder
+/// trit never encodes such an index, as encoders are not negative.
+const VERSION_MARKER: [Trit; 2 * TRITS_PER_TRYTE] = [1, 1, 1, -1, -1, -1];
+
+/// Layout of a payload, told apart by its first trytes
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub(crate) enum Version {
+    /// The original layout, without a message type or a tag. The message is
+    /// always signed, and numbers are encoded with `pascal::encode`
+    Baseline,
+    /// The payload starts with `VERSION_MARKER`, is followed by a tag, and
+    /// numbers are encoded with `pascal::encode_unsigned`
+    Compact,
+}
+
+impl Version {
+    /// Decodes a number at the start of `input`, returning it along with the
+    /// length of its encoding
+    pub(crate) fn decode(self, input: &[Trit]) -> Result<(usize, usize), MamError> {
+        let decoded = match self {
+            Version::Baseline => {
+                pascal::decode_i64(input).ok().and_then(|(value, length)| if value < 0 {
+                    None
+                } else {
+                    Some((value as u64, length))
+                })
+            }
+            Version::Compact => pascal::decode_unsigned(input).ok(),
+        };
+        match decoded {
+            Some((value, length)) if value <= usize::max_value() as u64 => {
+                Ok((value as usize, length))
+            }
+            _ => Err(MamError::ArrayOutOfBounds),
+        }
+    }
+
+    /// Reads a number from `reader`
+    pub(crate) fn read(self, reader: &mut Reader) -> Result<usize, MamError> {
+        let (value, length) = self.decode(reader.rest())?;
+        reader.read_trits(length)?;
+        Ok(value)
+    }
+}
+
+/// Length of a number as encoded in the payloads made by `create`
+pub(crate) fn number_length(value: usize) -> usize {
+    pascal::encoded_length_unsigned(value as u64)
+}
+
+/// Writes a number as in the payloads made by `create`
+pub(crate) fn write_number(value: usize, writer: &mut Writer) {
+    writer.write_unsigned(value as u64);
+}
+
+fn header_length(kind: MessageType, index: usize, message_length: usize) -> usize {
+    VERSION_MARKER.len() + number_length(kind.code()) + number_length(index) +
+        number_length(message_length)
+}
+
+fn write_header(kind: MessageType, index: usize, message_length: usize, writer: &mut Writer) {
+    writer.write_trits(&VERSION_MARKER);
+    write_number(kind.code(), writer);
+    write_number(index, writer);
+    write_number(message_length, writer);
+}
+
+/// Reads the version, message type, index and message length at the start
+/// of a `payload`, returning them along with the end of the header
+fn read_header(
+    payload: &[Trit],
+) -> Result<(Version, MessageType, usize, usize, usize), MamError> {
+    let mut reader = Reader::new(payload);
+    let (version, kind) = if reader.rest().starts_with(&VERSION_MARKER) {
+        reader.read_trits(VERSION_MARKER.len())?;
+        let kind = MessageType::from_code(Version::Compact.read(&mut reader)?)?;
+        (Version::Compact, kind)
+    } else {
+        (Version::Baseline, MessageType::Signed)
+    };
+    let index = version.read(&mut reader)?;
+    let message_length = version.read(&mut reader)?;
+    Ok((version, kind, index, message_length, reader.position()))
+}
+
 pub fn min_length(
     message_length: usize,
     siblings_length: usize,
     index: usize,
-    security: usize,
+    security: Security,
+) -> usize {
+    signed_min_length(
+        MessageType::Signed,
+        message_length,
+        siblings_length,
+        index,
+        security,
+    )
+}
+
+pub(crate) fn signed_min_length(
+    kind: MessageType,
+    message_length: usize,
+    siblings_length: usize,
+    index: usize,
+    security: Security,
 ) -> usize {
-    pascal::encoded_length(index as isize) +
-        pascal::encoded_length((HASH_LENGTH + message_length) as isize) + HASH_LENGTH +
-        message_length + MESSAGE_NONCE_LENGTH + security as usize * iss::KEY_LENGTH +
-        pascal::encoded_length((siblings_length / HASH_LENGTH) as isize) + siblings_length
+    header_length(kind, index, message_length) + HASH_LENGTH + message_length + NONCE_LENGTH +
+        security.signature_length() + number_length(siblings_length / HASH_LENGTH) +
+        siblings_length + TAG_LENGTH
+}
+
+/// Minimum length of a payload made by `create_unsigned`
+pub fn unsigned_min_length(message_length: usize, index: usize) -> usize {
+    header_length(MessageType::Unsigned, index, message_length) + HASH_LENGTH + message_length +
+        TAG_LENGTH
 }
 
 /// Creates a signed, encrypted payload from a `message`,
@@ -39,7 +228,11 @@ pub fn min_length(
 /// * a `encr_curl` instance of Trit Curl for use in encrypting the payload,
 /// * a `bcurl` instance of binary coded trits Curl for use in finding the hamming nonce
 ///
-/// Returns the signed, encrypted `payload`
+/// Use `prepare` and `finish` instead to run the nonce search with a budget
+/// or cancel it.
+///
+/// Returns the length of the signed, encrypted `payload`, or
+/// `MamError::NonceSearchFailed` if no hamming nonce was found
 pub fn create<C, CB, H>(
     seed: &[Trit],
     message: &[Trit],
@@ -49,39 +242,200 @@ pub fn create<C, CB, H>(
     next: &[Trit],
     start: isize,
     index: usize,
-    security: u8,
+    security: Security,
     payload: &mut [Trit],
     curl: &mut C,
     encr_curl: &mut C,
     bcurl: &mut CB,
-) -> usize
+) -> Result<usize, MamError>
 where
     C: Curl<Trit>,
     CB: Curl<BCTrit>,
     H: HammingNonce<Trit>,
 {
-    // generate the key and the get the merkle tree hashes
-    let message_length = message.len();
+    create_signed::<C, CB, H>(
+        MessageType::Signed,
+        seed,
+        message,
+        side_key,
+        root,
+        siblings,
+        next,
+        start,
+        index,
+        security,
+        payload,
+        curl,
+        encr_curl,
+        bcurl,
+    )
+}
+
+/// Creates a signed payload of the given `kind`, see `create`
+pub(crate) fn create_signed<C, CB, H>(
+    kind: MessageType,
+    seed: &[Trit],
+    message: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next: &[Trit],
+    start: isize,
+    index: usize,
+    security: Security,
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+{
+    let key = LeafKey::new(seed, start, index, security, curl);
+    finalize_signed::<C, CB, H, _>(
+        kind,
+        index,
+        security,
+        |hash, out, curl| {
+            key.sign(hash, out, curl);
+            Ok(())
+        },
+        &[],
+        message,
+        side_key,
+        root,
+        siblings,
+        next,
+        payload,
+        curl,
+        encr_curl,
+        bcurl,
+    )
+}
+
+/// Finishes publishing a message with a `key` generated ahead of time with
+/// `LeafKey::new`, which is used up.
+///
+/// Takes the same parameters as `create`, with the seed, `start`, `index`
+/// and `security` replaced by the `key`.
+///
+/// Returns the length of the signed, encrypted `payload`, or
+/// `MamError::NonceSearchFailed` if no hamming nonce was found
+pub fn finalize<C, CB, H>(
+    key: LeafKey,
+    message: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next: &[Trit],
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+{
+    let index = key.index();
+    let security = key.security();
+    finalize_signed::<C, CB, H, _>(
+        MessageType::Signed,
+        index,
+        security,
+        |hash, out, curl| {
+            key.sign(hash, out, curl);
+            Ok(())
+        },
+        &[],
+        message,
+        side_key,
+        root,
+        siblings,
+        next,
+        payload,
+        curl,
+        encr_curl,
+        bcurl,
+    )
+}
 
-    let index_p = pascal::encoded_length(index as isize);
-    let message_p = pascal::encoded_length(message_length as isize);
-
-    let siblings_length = siblings.len();
-    let siblings_count = (siblings.len() / HASH_LENGTH) as isize;
-    let siblings_pascal_length = pascal::encoded_length(siblings_count);
-    let signature_length = security as usize * iss::KEY_LENGTH;
-    let payload_min_length = message_p + HASH_LENGTH + message_length + MESSAGE_NONCE_LENGTH +
-        signature_length +
-        siblings_pascal_length + siblings_length + index_p;
-
-    let next_root_start = index_p + message_p;
-    let next_end = next_root_start + next.len();
-    let message_end = next_root_start + HASH_LENGTH + message_length;
-    let nonce_end = message_end + MESSAGE_NONCE_LENGTH;
-    let signature_end = nonce_end + signature_length;
-    let siblings_pascal_end = signature_end + siblings_pascal_length;
-    let siblings_end = siblings_pascal_end + siblings_length;
+/// Creates a signed, encrypted payload from a `message`, signed by the
+/// `signer` with the key of the leaf at `index`.
+///
+/// Takes the same parameters as `create`, with the seed, `start` and
+/// `security` replaced by the `signer`.
+///
+/// Returns the length of the signed, encrypted `payload`, the error of the
+/// `signer`, or `MamError::NonceSearchFailed` if no hamming nonce was found
+pub fn create_with_signer<C, CB, H, S>(
+    signer: &mut S,
+    message: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next: &[Trit],
+    index: usize,
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+    S: Signer,
+{
+    let security = signer.security();
+    finalize_signed::<C, CB, H, _>(
+        MessageType::Signed,
+        index,
+        security,
+        |hash, out, _| signer.sign(index, hash, out),
+        &[],
+        message,
+        side_key,
+        root,
+        siblings,
+        next,
+        payload,
+        curl,
+        encr_curl,
+        bcurl,
+    )
+}
 
+/// Creates a signed payload of the given `kind`, signed with `sign`, with
+/// the `certificate` of a layered message appended after the siblings
+pub(crate) fn finalize_signed<C, CB, H, F>(
+    kind: MessageType,
+    index: usize,
+    security: Security,
+    sign: F,
+    certificate: &[Trit],
+    message: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next: &[Trit],
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+    F: FnOnce(&[Trit], &mut [Trit], &mut C) -> Result<(), MamError>,
+{
+    let payload_min_length =
+        signed_min_length(kind, message.len(), siblings.len(), index, security) +
+            certificate.len();
     assert!(
         payload.len() >= payload_min_length,
         "should be: {}, is {}",
@@ -89,50 +443,262 @@ where
         payload.len()
     );
 
-    encr_curl.absorb(side_key);
-    encr_curl.absorb(root);
-    pascal::encode(index as isize, &mut payload[..index_p]);
-    pascal::encode(
-        message_length as isize,
-        &mut payload[index_p..next_root_start],
+    let message_end = prepare_signed(
+        kind,
+        message,
+        side_key,
+        root,
+        next,
+        index,
+        payload,
+        encr_curl,
     );
-    encr_curl.absorb(&payload[..next_root_start]);
-    payload[next_root_start..next_end].clone_from_slice(&next);
-    payload[next_end..message_end].clone_from_slice(&message);
-    mask_slice(&mut payload[next_root_start..message_end], encr_curl);
     curl.state_mut().clone_from_slice(&encr_curl.state());
-    H::search(security, 0, HASH_LENGTH / 3, curl, bcurl).unwrap();
-    payload[message_end..nonce_end].clone_from_slice(&curl.rate()[..MESSAGE_NONCE_LENGTH]);
-    mask_slice(&mut payload[message_end..nonce_end], encr_curl);
+    let found = H::search(security.level() as u8, 0, NONCE_LENGTH, curl, bcurl).is_some();
+    let mut nonce: [Trit; NONCE_LENGTH] = [0; NONCE_LENGTH];
+    nonce.clone_from_slice(&curl.rate()[..NONCE_LENGTH]);
     bcurl.reset();
     curl.reset();
-    iss::subseed(
-        seed,
-        start + index as isize,
-        &mut payload[nonce_end..nonce_end + HASH_LENGTH],
+    if !found {
+        encr_curl.reset();
+        return Err(MamError::NonceSearchFailed);
+    }
+    finish_signed(
+        &nonce,
+        security,
+        sign,
+        certificate,
+        siblings,
+        message_end,
+        payload,
         curl,
-    );
-    curl.reset();
-    iss::key(
-        &mut payload[nonce_end..signature_end],
-        security as usize,
+        encr_curl,
+    )
+}
+
+/// Writes the header, then the masked next root and message, returning the
+/// end of the message. Leaves `encr_curl` ready for the nonce search.
+fn prepare_signed<C: Curl<Trit>>(
+    kind: MessageType,
+    message: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    next: &[Trit],
+    index: usize,
+    payload: &mut [Trit],
+    encr_curl: &mut C,
+) -> usize {
+    debug_assert!(kind != MessageType::Unsigned);
+    let mut writer = Writer::new(payload);
+    write_header(kind, index, message.len(), &mut writer);
+    let next_root_start = writer.position();
+
+    encr_curl.absorb(side_key);
+    encr_curl.absorb(root);
+    encr_curl.absorb(writer.written(0));
+    writer.write_trits(next);
+    writer.write_trits(message);
+    mask_slice(writer.written(next_root_start), encr_curl);
+    writer.position()
+}
+
+/// Masks the `nonce`, signs the payload prepared up to `message_end` with
+/// `sign` and appends the masked signature, siblings, certificate and tag,
+/// returning the payload length
+fn finish_signed<C, F>(
+    nonce: &[Trit],
+    security: Security,
+    sign: F,
+    certificate: &[Trit],
+    siblings: &[Trit],
+    message_end: usize,
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    F: FnOnce(&[Trit], &mut [Trit], &mut C) -> Result<(), MamError>,
+{
+    let mut writer = Writer::new(&mut payload[message_end..]);
+    writer.write_trits(nonce);
+    mask_slice(writer.written(0), encr_curl);
+    let signature_start = writer.position();
+    if let Err(e) = sign(
+        encr_curl.rate(),
+        writer.reserve(security.signature_length()),
         curl,
+    )
+    {
+        encr_curl.reset();
+        return Err(e);
+    }
+    write_number(siblings.len() / HASH_LENGTH, &mut writer);
+    writer.write_trits(siblings);
+    mask_slice(writer.written(signature_start), encr_curl);
+    if !certificate.is_empty() {
+        let certificate_start = writer.position();
+        writer.write_trits(certificate);
+        if let Err(e) =
+            layered::mask_certificate(writer.written(certificate_start), security, encr_curl)
+        {
+            encr_curl.reset();
+            return Err(e);
+        }
+    }
+    tag(writer.reserve(TAG_LENGTH), encr_curl);
+    encr_curl.reset();
+    Ok(message_end + writer.position())
+}
+
+/// A signed payload whose header, next root and message have been masked,
+/// waiting for its hamming nonce to be found before it can be finished
+pub struct Prepared {
+    search: NonceSearch,
+    index: usize,
+    message_end: usize,
+}
+
+impl Prepared {
+    /// The nonce search to run, e.g. in slices with `NonceSearch::run`
+    pub fn search(&mut self) -> &mut NonceSearch {
+        &mut self.search
+    }
+}
+
+/// Prepares a signed, encrypted payload from a `message` as the first step of
+/// `create`, writing everything up to the hamming nonce to `payload`.
+///
+/// `encr_curl` holds the state of the encryption and must be passed on
+/// unchanged to `finish`.
+pub fn prepare<C: Curl<Trit>>(
+    message: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    next: &[Trit],
+    index: usize,
+    security: Security,
+    payload: &mut [Trit],
+    encr_curl: &mut C,
+) -> Prepared {
+    let message_end = header_length(MessageType::Signed, index, message.len()) + HASH_LENGTH +
+        message.len();
+    assert!(
+        payload.len() >= message_end,
+        "should be: {}, is {}",
+        message_end,
+        payload.len()
     );
-    curl.reset();
-    iss::signature(
-        &encr_curl.rate(),
-        &mut payload[nonce_end..signature_end],
-        curl,
+    let message_end = prepare_signed(
+        MessageType::Signed,
+        message,
+        side_key,
+        root,
+        next,
+        index,
+        payload,
+        encr_curl,
     );
-    curl.reset();
-    pascal::encode(
-        siblings_count,
-        &mut payload[signature_end..siblings_pascal_end],
+    Prepared {
+        search: NonceSearch::new(security, encr_curl),
+        index: index,
+        message_end: message_end,
+    }
+}
+
+/// Finishes a `prepared` payload once its nonce has been found, signing it
+/// with the leaf `key` of the same index and security, and appending the
+/// `siblings`.
+///
+/// Returns the length of the payload, `MamError::NonceSearchFailed` if no
+/// nonce has been found yet, or `MamError::KeyMismatch` if the `key` does not
+/// belong to the prepared message
+pub fn finish<C: Curl<Trit>>(
+    prepared: &Prepared,
+    key: LeafKey,
+    siblings: &[Trit],
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+) -> Result<usize, MamError> {
+    let security = prepared.search.security();
+    if key.index() != prepared.index || key.security() != security {
+        return Err(MamError::KeyMismatch);
+    }
+    let payload_min_length = prepared.message_end + NONCE_LENGTH + security.signature_length() +
+        number_length(siblings.len() / HASH_LENGTH) + siblings.len() + TAG_LENGTH;
+    assert!(
+        payload.len() >= payload_min_length,
+        "should be: {}, is {}",
+        payload_min_length,
+        payload.len()
     );
-    payload[siblings_pascal_end..siblings_end].clone_from_slice(&siblings);
-    mask_slice(&mut payload[nonce_end..siblings_end], encr_curl);
+    match prepared.search.nonce() {
+        Some(nonce) => {
+            finish_signed(
+                nonce,
+                security,
+                |hash, out, curl| {
+                    key.sign(hash, out, curl);
+                    Ok(())
+                },
+                &[],
+                siblings,
+                prepared.message_end,
+                payload,
+                curl,
+                encr_curl,
+            )
+        }
+        None => Err(MamError::NonceSearchFailed),
+    }
+}
+
+/// Creates an unsigned, encrypted payload from a `message`, which skips the
+/// nonce search, signature and merkle branch of `create` and is authenticated
+/// by its tag alone.
+///
+/// * a `side_key`, which is used for encryption and authentication,
+/// * a merkle `root` which used as an initialization vector for the encryption,
+/// * the `next` merkle root which is copied to the message,
+/// * the `index` of the message, which must not be reused under the same `root`,
+/// * a `encr_curl` instance of Trit Curl for use in encrypting the payload
+///
+/// Returns the length of the encrypted `payload`
+pub fn create_unsigned<C>(
+    message: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    next: &[Trit],
+    index: usize,
+    payload: &mut [Trit],
+    encr_curl: &mut C,
+) -> usize
+where
+    C: Curl<Trit>,
+{
+    let message_length = message.len();
+    let payload_min_length = unsigned_min_length(message_length, index);
+    assert!(
+        payload.len() >= payload_min_length,
+        "should be: {}, is {}",
+        payload_min_length,
+        payload.len()
+    );
+
+    let mut writer = Writer::new(payload);
+    write_header(MessageType::Unsigned, index, message_length, &mut writer);
+    let next_root_start = writer.position();
+
+    encr_curl.absorb(side_key);
+    encr_curl.absorb(root);
+    encr_curl.absorb(writer.written(0));
+    writer.write_trits(next);
+    writer.write_trits(message);
+    mask_slice(writer.written(next_root_start), encr_curl);
+    tag(writer.reserve(TAG_LENGTH), encr_curl);
     encr_curl.reset();
-    payload_min_length
+    writer.position()
 }
 
 /// Parses an encrypted `payload`, first decrypting it with a
@@ -143,78 +709,177 @@ where
 ///
 /// as initialization vector.
 ///
-/// Then checks that the signature is valid and with sibling hashes in the payload
+/// Then checks the authentication tag over the unmasked payload, so that
+/// tampered payloads or a wrong `side_key` are rejected before the signature
+/// is digested, and that the signature is valid and with sibling hashes in the payload
 /// resolves to the merkle `root`.
 ///
-/// Returns the `message` contained therein if valid, or a MamError if invalid
+/// Payloads made before message types and tags were added carry no tag, and
+/// are only checked against their signature.
+///
+/// Payloads made by `create_unsigned` are only checked against their tag.
+/// For payloads made by `layered::create`, the siblings resolve to a subtree
+/// root, whose certificate must in turn resolve to the merkle `root`.
+///
+/// Returns the location of the `message` contained therein if valid, or a
+/// MamError if invalid
 pub fn parse<C>(
     payload: &mut [Trit],
     side_key: &[Trit],
     root: &[Trit],
     curl: &mut C,
-) -> Result<(usize, usize), MamError>
+) -> Result<Parsed, MamError>
+where
+    C: Curl<Trit>,
+{
+    parse_payload(payload, side_key, root, None, curl)
+}
+
+/// Parses an encrypted `payload` like `parse`, but rejects messages signed
+/// below the `min_security` level, as well as unsigned messages, with
+/// `MamError::SecurityTooLow` before the signature is digested
+pub fn parse_min_security<C>(
+    payload: &mut [Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    min_security: Security,
+    curl: &mut C,
+) -> Result<Parsed, MamError>
+where
+    C: Curl<Trit>,
+{
+    parse_payload(payload, side_key, root, Some(min_security), curl)
+}
+
+fn parse_payload<C>(
+    payload: &mut [Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    min_security: Option<Security>,
+    curl: &mut C,
+) -> Result<Parsed, MamError>
+where
+    C: Curl<Trit>,
+{
+    let res = read_payload(payload, side_key, root, min_security, curl);
+    curl.reset();
+    res
+}
+
+/// Unmasks and verifies a `payload` for `parse_payload`, which resets `curl`
+/// afterwards whether or not the payload is valid
+fn read_payload<C>(
+    payload: &mut [Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    min_security: Option<Security>,
+    curl: &mut C,
+) -> Result<Parsed, MamError>
 where
     C: Curl<Trit>,
 {
-    let (index, message_length, next_root_start) = {
-        let (index, index_end) = pascal::decode(&payload);
-        let (message_length, message_length_end) = pascal::decode(&payload[index_end..]);
-        (
-            index as usize,
-            message_length as usize,
-            (index_end + message_length_end) as usize,
-        )
+    let (version, kind, index, message_length, header_end) = read_header(payload)?;
+    if kind.carries_root() && message_length < HASH_LENGTH {
+        return Err(MamError::ArrayOutOfBounds);
+    }
+    if kind == MessageType::Unsigned && min_security.is_some() {
+        return Err(MamError::SecurityTooLow);
+    }
+    let mut reader = ReaderMut::new(payload);
+    let header = reader.read_trits(header_end)?;
+    let tag_length = match version {
+        Version::Baseline => 0,
+        Version::Compact => TAG_LENGTH,
     };
-    let message_start = next_root_start + HASH_LENGTH;
-    let message_end = message_start + message_length;
+    if message_length > reader.remaining() ||
+        HASH_LENGTH + message_length + tag_length > reader.remaining()
+    {
+        return Err(MamError::ArrayOutOfBounds);
+    }
 
     curl.absorb(side_key);
     curl.absorb(root);
-    if message_length as usize > payload.len() {
-        return Err(MamError::ArrayOutOfBounds);
-    }
-    curl.absorb(&payload[..next_root_start]);
+    curl.absorb(header);
 
-    let mut pos = {
-        unmask_slice(&mut payload[next_root_start..message_start], curl);
-        unmask_slice(&mut payload[message_start..message_end], curl);
-        message_end as usize
+    unmask_slice(reader.read_trits(HASH_LENGTH)?, curl);
+    unmask_slice(reader.read_trits(message_length)?, curl);
+    let mut parsed = Parsed {
+        kind: kind,
+        index: index,
+        security: None,
+        start: header_end,
+        end: reader.position(),
     };
-    unmask_slice(&mut payload[pos..pos + MESSAGE_NONCE_LENGTH], curl);
-    pos += HASH_LENGTH / 3;
+    if kind == MessageType::Unsigned {
+        return if verify_tag(reader.read_trits(TAG_LENGTH)?, curl) {
+            Ok(parsed)
+        } else {
+            Err(MamError::InvalidTag)
+        };
+    }
+    unmask_slice(reader.read_trits(NONCE_LENGTH)?, curl);
     let mut hmac: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
     hmac.clone_from_slice(&curl.rate());
-    let security = iss::checksum_security(&hmac);
-    unmask_slice(&mut payload[pos..], curl);
-    if security != 0 {
-        let sig_end = pos + security * iss::KEY_LENGTH;
-        iss::digest_bundle_signature(&hmac, &mut payload[pos..sig_end], curl);
-        hmac.clone_from_slice(&curl.rate());
-        curl.reset();
-        pos = sig_end;
-        let l = pascal::decode(&payload[pos..]);
-
-        curl.absorb(&hmac);
-        if l.0 != 0 {
-            // get address lite
-            hmac.clone_from_slice(curl.rate());
-            pos += l.1;
-            let sib_end = pos + l.0 as usize * HASH_LENGTH;
-            let siblings = &payload[pos..sib_end];
-            curl.reset();
-            merkle::root(&hmac, siblings, index as usize, curl);
-        };
+    let security = Security::from_level(iss::checksum_security(&hmac))
+        .map_err(|_| MamError::InvalidHash)?;
+    if min_security.map_or(false, |min| security < min) {
+        return Err(MamError::SecurityTooLow);
+    }
+    parsed.security = Some(security);
+    let signature = reader.read_trits(security.signature_length())?;
+    unmask_slice(signature, curl);
+    let (siblings_count, count_length) = {
+        let mut encoded: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        let len = cmp::min(HASH_LENGTH, reader.remaining());
+        peek_slice(reader.rest(), &mut encoded[..len], curl);
+        version.decode(&encoded[..len])?
+    };
+    if siblings_count > reader.remaining() / HASH_LENGTH {
+        return Err(MamError::ArrayOutOfBounds);
+    }
+    let siblings = reader.read_trits(count_length + siblings_count * HASH_LENGTH)?;
+    unmask_slice(siblings, curl);
+    let siblings = &siblings[count_length..];
+    let certificate = if kind == MessageType::Layered {
+        Some(layered::unmask_certificate(
+            &mut reader,
+            version,
+            security,
+            curl,
+        )?)
+    } else {
+        None
+    };
+    if version == Version::Compact && !verify_tag(reader.read_trits(TAG_LENGTH)?, curl) {
+        return Err(MamError::InvalidTag);
+    }
+    curl.reset();
 
-        let res = if curl.rate() == root {
-            Ok((next_root_start, message_end))
-        } else {
-            Err(MamError::InvalidSignature)
-        };
+    iss::digest_bundle_signature(&hmac, signature, curl);
+    hmac.clone_from_slice(&curl.rate());
+    curl.reset();
+
+    curl.absorb(&hmac);
+    if siblings_count != 0 {
+        // get address lite
+        hmac.clone_from_slice(curl.rate());
         curl.reset();
-        res
+        merkle::root(&hmac, siblings, index as usize, curl);
+    };
+
+    let valid = match certificate {
+        Some(certificate) => {
+            let mut sub_root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+            sub_root.clone_from_slice(curl.rate());
+            curl.reset();
+            layered::verify_certificate(certificate, &sub_root, security, root, curl)
+        }
+        None => ct_eq(curl.rate(), root),
+    };
+    if valid {
+        Ok(parsed)
     } else {
-        curl.reset();
-        Err(MamError::InvalidHash)
+        Err(MamError::InvalidSignature)
     }
 }
 
@@ -223,6 +888,7 @@ mod tests {
     use super::*;
     use curl_cpu::*;
     use alloc::Vec;
+    use quickcheck::{QuickCheck, TestResult};
 
     #[test]
     fn single_leaf_tree() {
@@ -242,7 +908,7 @@ mod tests {
             .flat_map(char_to_trits)
             .cloned()
             .collect();
-        let security: u8 = 1;
+        let security = Security::Low;
         let start: isize = 0;
         let count: usize = 1;
         let next_start = start + count as isize;
@@ -257,7 +923,7 @@ mod tests {
             &seed,
             start,
             count,
-            security as usize,
+            security.level(),
             &mut c1,
             &mut c2,
             &mut c3,
@@ -266,7 +932,7 @@ mod tests {
             &seed,
             next_start,
             next_count,
-            security as usize,
+            security.level(),
             &mut c1,
             &mut c2,
             &mut c3,
@@ -289,7 +955,7 @@ mod tests {
         }
 
         let mut payload: Vec<Trit> =
-            vec![0; min_length(message.len(), siblings.len(), index, security as usize) + 1];
+            vec![0; min_length(message.len(), siblings.len(), index, security) + 1];
 
         create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
             &seed,
@@ -305,7 +971,7 @@ mod tests {
             &mut c1,
             &mut c2,
             &mut bc,
-        );
+        ).unwrap();
 
         let pstr = trits_to_string(&payload).unwrap();
         c1.reset();
@@ -316,9 +982,26 @@ mod tests {
             .cloned()
             .collect();
 
+        let mut strong_payload = payload.clone();
+        assert_eq!(
+            parse_min_security(
+                &mut strong_payload,
+                &side_key,
+                &root_trits,
+                Security::Medium,
+                &mut c1,
+            ),
+            Err(MamError::SecurityTooLow)
+        );
+        let mut low_payload = payload.clone();
+        assert_eq!(
+            parse_min_security(&mut low_payload, &side_key, &root_trits, security, &mut c1)
+                .map(|parsed| parsed.security),
+            Ok(Some(security))
+        );
 
         match parse(&mut payload, &side_key, &root_trits, &mut c1) {
-            Ok((s, len)) => {
+            Ok(Parsed { start: s, end: len, .. }) => {
                 assert_eq!(
                     trits_to_string(&payload[s + HASH_LENGTH..len]),
                     trits_to_string(&message)
@@ -354,7 +1037,7 @@ mod tests {
             .flat_map(char_to_trits)
             .cloned()
             .collect();
-        let security: u8 = 1;
+        let security = Security::Low;
         let start: isize = 1;
         let count: usize = 4;
         let next_start = start + count as isize;
@@ -369,7 +1052,7 @@ mod tests {
             &seed,
             start,
             count,
-            security as usize,
+            security.level(),
             &mut c1,
             &mut c2,
             &mut c3,
@@ -378,7 +1061,7 @@ mod tests {
             &seed,
             next_start,
             next_count,
-            security as usize,
+            security.level(),
             &mut c1,
             &mut c2,
             &mut c3,
@@ -402,7 +1085,7 @@ mod tests {
             merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
 
             let mut payload: Vec<Trit> =
-                vec![0; min_length(message.len(), siblings.len(), index, security as usize)];
+                vec![0; min_length(message.len(), siblings.len(), index, security)];
 
             create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
                 &seed,
@@ -418,12 +1101,12 @@ mod tests {
                 &mut c1,
                 &mut c2,
                 &mut bc,
-            );
+            ).unwrap();
 
             c1.reset();
 
             match parse(&mut payload, &side_key, &root_trits, &mut c1) {
-                Ok((s, len)) => {
+                Ok(Parsed { start: s, end: len, .. }) => {
                     assert_eq!(
                         trits_to_string(&payload[s + HASH_LENGTH..len]),
                         trits_to_string(&message)
@@ -440,4 +1123,351 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn parses_baseline_payloads() {
+        let seed: Vec<Trit> = "BASELINESEED".chars().flat_map(char_to_trits).cloned().collect();
+        let message: Vec<Trit> = "BASELINEMESSAGE".chars().flat_map(char_to_trits).cloned().collect();
+        let side_key: Vec<Trit> = "BASELINESIDEKEY".chars().flat_map(char_to_trits).cloned().collect();
+        let next: [Trit; HASH_LENGTH] = [-1; HASH_LENGTH];
+        let security = Security::Low;
+        let start: isize = 0;
+        let index: usize = 1;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let tree = merkle::create(&seed, start, 2, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&tree, &mut root);
+        let branch = merkle::branch(&tree, index);
+        let siblings_length = merkle::len(&branch) * HASH_LENGTH;
+        let mut siblings: Vec<Trit> = vec![0; siblings_length];
+        merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
+        let siblings_count = (siblings_length / HASH_LENGTH) as isize;
+
+        // Lay out the payload as before message types and tags were added
+        let index_end = pascal::encoded_length(index as isize);
+        let next_root_start = index_end + pascal::encoded_length(message.len() as isize);
+        let message_start = next_root_start + HASH_LENGTH;
+        let message_end = message_start + message.len();
+        let nonce_end = message_end + NONCE_LENGTH;
+        let signature_end = nonce_end + security.signature_length();
+        let siblings_start = signature_end + pascal::encoded_length(siblings_count);
+        let mut payload: Vec<Trit> = vec![0; siblings_start + siblings_length];
+        pascal::encode(index as isize, &mut payload[..index_end]);
+        pascal::encode(
+            message.len() as isize,
+            &mut payload[index_end..next_root_start],
+        );
+        assert!(!payload.starts_with(&VERSION_MARKER));
+
+        let mut encr_curl = CpuCurl::<Trit>::default();
+        encr_curl.absorb(&side_key);
+        encr_curl.absorb(&root);
+        encr_curl.absorb(&payload[..next_root_start]);
+        payload[next_root_start..message_start].clone_from_slice(&next);
+        payload[message_start..message_end].clone_from_slice(&message);
+        mask_slice(&mut payload[next_root_start..message_end], &mut encr_curl);
+        c1.state_mut().clone_from_slice(encr_curl.state());
+        CpuHam::search(security.level() as u8, 0, NONCE_LENGTH, &mut c1, &mut bc).unwrap();
+        payload[message_end..nonce_end].clone_from_slice(&c1.rate()[..NONCE_LENGTH]);
+        mask_slice(&mut payload[message_end..nonce_end], &mut encr_curl);
+        c1.reset();
+        LeafKey::new(&seed, start, index, security, &mut c1).sign(
+            encr_curl.rate(),
+            &mut payload[nonce_end..signature_end],
+            &mut c1,
+        );
+        pascal::encode(
+            siblings_count,
+            &mut payload[signature_end..siblings_start],
+        );
+        payload[siblings_start..].clone_from_slice(&siblings);
+        mask_slice(&mut payload[nonce_end..], &mut encr_curl);
+
+        let mut tampered = payload.clone();
+        let parsed = parse(&mut payload, &side_key, &root, &mut c1).unwrap();
+        assert_eq!(parsed.kind, MessageType::Signed);
+        assert_eq!(parsed.security, Some(security));
+        assert_eq!(parsed.next(&payload), &next[..]);
+        assert_eq!(parsed.message(&payload), &message[..]);
+
+        let last = tampered.len() - 1;
+        tampered[last] = if tampered[last] == 1 { 0 } else { 1 };
+        assert!(parse(&mut tampered, &side_key, &root, &mut c1).is_err());
+    }
+
+    #[test]
+    fn unsigned_message() {
+        let message: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let side_key: Vec<Trit> = "EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let root: Vec<Trit> = "MYMERKLEROOTHASH".chars().flat_map(char_to_trits).cloned().collect();
+        let next_root: Vec<Trit> = "MYNEXTMERKLEROOTHASH"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let index = 7;
+        let mut c1 = CpuCurl::<Trit>::default();
+
+        let mut payload: Vec<Trit> = vec![0; unsigned_min_length(message.len(), index)];
+        create_unsigned(
+            &message,
+            &side_key,
+            &root,
+            &next_root,
+            index,
+            &mut payload,
+            &mut c1,
+        );
+
+        let mut tampered = payload.clone();
+        let parsed = parse(&mut payload, &side_key, &root, &mut c1).unwrap();
+        assert_eq!(parsed.kind, MessageType::Unsigned);
+        assert_eq!(parsed.message(&payload), &message[..]);
+        assert_eq!(&parsed.next(&payload)[..next_root.len()], &next_root[..]);
+
+        assert_eq!(parsed.security, None);
+        let mut strict = tampered.clone();
+        assert_eq!(
+            parse_min_security(&mut strict, &side_key, &root, Security::Low, &mut c1),
+            Err(MamError::SecurityTooLow)
+        );
+
+        let last = tampered.len() - TAG_LENGTH - 1;
+        tampered[last] = if tampered[last] == 1 { 0 } else { 1 };
+        assert_eq!(
+            parse(&mut tampered, &side_key, &root, &mut c1),
+            Err(MamError::InvalidTag)
+        );
+    }
+
+    #[test]
+    fn prepared_nonce_search() {
+        let seed: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let message: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let side_key: Vec<Trit> = "EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let security = Security::Low;
+        let start: isize = 0;
+        let index = 0;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+
+        let root = merkle::create(&seed, start, 1, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root_trits: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&root, &mut root_trits);
+
+        let mut payload: Vec<Trit> = vec![0; min_length(message.len(), 0, index, security)];
+        let mut prepared = prepare(
+            &message,
+            &side_key,
+            &root_trits,
+            &root_trits,
+            index,
+            security,
+            &mut payload,
+            &mut c2,
+        );
+        assert_eq!(
+            finish(
+                &prepared,
+                LeafKey::new(&seed, start, index, security, &mut c1),
+                &[],
+                &mut payload,
+                &mut c1,
+                &mut c2,
+            ),
+            Err(MamError::NonceSearchFailed)
+        );
+        assert_eq!(
+            finish(
+                &prepared,
+                LeafKey::new(&seed, start, index + 1, security, &mut c1),
+                &[],
+                &mut payload,
+                &mut c1,
+                &mut c2,
+            ),
+            Err(MamError::KeyMismatch)
+        );
+        assert_eq!(
+            prepared.search().run(10000, &mut c1, |_| true),
+            Err(MamError::NonceSearchFailed)
+        );
+        while prepared.search().run(10, &mut c1, |_| false).is_err() {}
+        let key = LeafKey::new(&seed, start, index, security, &mut c1);
+        finish(&prepared, key, &[], &mut payload, &mut c1, &mut c2).unwrap();
+
+        let parsed = parse(&mut payload, &side_key, &root_trits, &mut c1).unwrap();
+        assert_eq!(parsed.message(&payload), &message[..]);
+    }
+
+    #[test]
+    fn precomputed_leaf_keys() {
+        let seed: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let message: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let side_key: Vec<Trit> = "EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let security = Security::Medium;
+        let start: isize = 3;
+        let count: usize = 2;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let root = merkle::create(&seed, start, count, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root_trits: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&root, &mut root_trits);
+
+        let keys: Vec<LeafKey> = (0..count)
+            .map(|index| LeafKey::new(&seed, start, index, security, &mut c1))
+            .collect();
+        for key in keys {
+            let index = key.index();
+            let branch = merkle::branch(&root, index);
+            let siblings_length = merkle::len(&branch) * HASH_LENGTH;
+            let mut siblings: Vec<Trit> = vec![0; siblings_length];
+            merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
+
+            let mut payload: Vec<Trit> =
+                vec![0; min_length(message.len(), siblings.len(), index, security)];
+            finalize::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+                key,
+                &message,
+                &side_key,
+                &root_trits,
+                &siblings,
+                &root_trits,
+                &mut payload,
+                &mut c1,
+                &mut c2,
+                &mut bc,
+            ).unwrap();
+
+            let parsed = parse(&mut payload, &side_key, &root_trits, &mut c1).unwrap();
+            assert_eq!(parsed.message(&payload), &message[..]);
+        }
+    }
+
+    /// Creates and parses a payload for a random message, tree size, index
+    /// and security level, then checks that flipping the trit at `pos` makes
+    /// it fail to parse
+    fn round_trips_and_rejects_flips(
+        message: Vec<u8>,
+        count: u8,
+        index: u8,
+        level: u8,
+        pos: usize,
+    ) -> TestResult {
+        let seed: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let side_key: Vec<Trit> = "EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let message: Vec<Trit> = message.iter().map(|&b| (b % 3) as Trit - 1).collect();
+        let count = count as usize % 4 + 1;
+        let index = index as usize % count;
+        let security = Security::from_level(level as usize % 3 + 1).unwrap();
+        let start: isize = 0;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let tree = merkle::create(&seed, start, count, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&tree, &mut root);
+        let branch = merkle::branch(&tree, index);
+        let siblings_length = merkle::len(&branch) * HASH_LENGTH;
+        let mut siblings: Vec<Trit> = vec![0; siblings_length];
+        if siblings_length > 0 {
+            merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
+        }
+
+        let mut payload: Vec<Trit> =
+            vec![0; min_length(message.len(), siblings.len(), index, security)];
+        let length = match create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &message,
+            &side_key,
+            &root,
+            &siblings,
+            &root,
+            start,
+            index,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ) {
+            Ok(length) => length,
+            Err(MamError::NonceSearchFailed) => return TestResult::discard(),
+            Err(_) => return TestResult::failed(),
+        };
+        payload.truncate(length);
+        let mut flipped = payload.clone();
+
+        let parsed = parse(&mut payload, &side_key, &root, &mut c1);
+        match parsed {
+            Ok(ref parsed) if parsed.message(&payload) == &message[..] &&
+                                  parsed.security == Some(security) => {}
+            _ => return TestResult::failed(),
+        }
+
+        let pos = pos % flipped.len();
+        flipped[pos] = if flipped[pos] == 1 { -1 } else { flipped[pos] + 1 };
+        TestResult::from_bool(parse(&mut flipped, &side_key, &root, &mut c1).is_err())
+    }
+
+    #[test]
+    fn random_round_trips() {
+        QuickCheck::new().tests(10).quickcheck(
+            round_trips_and_rejects_flips as fn(Vec<u8>, u8, u8, u8, usize) -> TestResult,
+        );
+    }
 }
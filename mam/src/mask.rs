@@ -1,6 +1,10 @@
 use trytes::*;
 use tmath::*;
 use curl::*;
+use ct::*;
+
+/// Length of the authentication tag squeezed after masking
+pub const TAG_LENGTH: usize = HASH_LENGTH / 3;
 
 pub fn mask<C>(payload: &mut [Trit], key: &[Trit], curl: &mut C)
 where
@@ -65,6 +69,37 @@ where
     }
 }
 
+/// Unmasks the start of `payload` into `out` without advancing the sponge,
+/// e.g. to read a length prefix before unmasking the rest of the payload
+pub fn peek_slice<C>(payload: &[Trit], out: &mut [Trit], curl: &C)
+where
+    C: Curl<Trit>,
+{
+    for (o, (&p, &k)) in out.iter_mut().zip(payload.iter().zip(curl.rate())) {
+        *o = trit_sum(p, -k);
+    }
+}
+
+/// Squeezes an authentication tag over everything masked or unmasked so far
+/// into `out`
+pub fn tag<C>(out: &mut [Trit], curl: &mut C)
+where
+    C: Curl<Trit>,
+{
+    curl.squeeze(out);
+}
+
+/// Squeezes the authentication tag over everything unmasked so far and
+/// checks it against `expected`
+pub fn verify_tag<C>(expected: &[Trit], curl: &mut C) -> bool
+where
+    C: Curl<Trit>,
+{
+    let mut actual: [Trit; TAG_LENGTH] = [0; TAG_LENGTH];
+    tag(&mut actual, curl);
+    ct_eq(expected, &actual)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,4 +127,96 @@ mod tests {
         unmask::<CpuCurl<Trit>>(&mut cipher, &keys, &mut curl);
         assert_eq!(trits_to_string(&payload), trits_to_string(&cipher));
     }
+
+    #[test]
+    fn tag_detects_tampering() {
+        let payload: Vec<Trit> = "AMESSAGEFORYOU9AMESSAGEFORYOU9AMESSAGEFORYOU9AMESSAGEFORYOU9AMESSAGEFORYOU9AMESSAGEFORYOU9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let key: Vec<Trit> = "MYSIDEKEY".chars().flat_map(char_to_trits).cloned().collect();
+        let other_key: Vec<Trit> = "MYSIDEKEZ".chars().flat_map(char_to_trits).cloned().collect();
+        let mut curl = CpuCurl::<Trit>::default();
+        let mut cipher = payload.clone();
+        let mut cipher_tag: [Trit; TAG_LENGTH] = [0; TAG_LENGTH];
+        curl.absorb(&key);
+        mask_slice(&mut cipher, &mut curl);
+        tag(&mut cipher_tag, &mut curl);
+        curl.reset();
+
+        let mut plain = cipher.clone();
+        curl.absorb(&key);
+        unmask_slice(&mut plain, &mut curl);
+        assert!(verify_tag(&cipher_tag, &mut curl));
+        assert_eq!(plain, payload);
+        curl.reset();
+
+        let mut plain = cipher.clone();
+        curl.absorb(&other_key);
+        unmask_slice(&mut plain, &mut curl);
+        assert!(!verify_tag(&cipher_tag, &mut curl));
+        curl.reset();
+
+        let mut plain = cipher.clone();
+        plain[100] = -plain[100] + if plain[100] == 0 { 1 } else { 0 };
+        curl.absorb(&key);
+        unmask_slice(&mut plain, &mut curl);
+        assert!(!verify_tag(&cipher_tag, &mut curl));
+    }
+    fn random_trits(bytes: &[u8]) -> Vec<Trit> {
+        bytes
+            .iter()
+            .flat_map(|&b| {
+                let mut rest = b as usize;
+                (0..5).map(move |_| {
+                    let t = (rest % 3) as Trit - 1;
+                    rest /= 3;
+                    t
+                })
+            })
+            .collect()
+    }
+
+    quickcheck! {
+        fn unmasks_random_payloads(payload: Vec<u8>, key: Vec<u8>) -> bool {
+            let payload = random_trits(&payload);
+            let key = random_trits(&key);
+            let mut curl = CpuCurl::<Trit>::default();
+            let mut cipher = payload.clone();
+            mask(&mut cipher, &key, &mut curl);
+            curl.reset();
+            unmask(&mut cipher, &key, &mut curl);
+            cipher == payload
+        }
+
+        fn tag_rejects_flipped_trits(payload: Vec<u8>, key: Vec<u8>, pos: usize) -> bool {
+            let payload = random_trits(&payload);
+            let key = random_trits(&key);
+            let mut curl = CpuCurl::<Trit>::default();
+            let mut cipher = payload.clone();
+            let mut cipher_tag: [Trit; TAG_LENGTH] = [0; TAG_LENGTH];
+            curl.absorb(&key);
+            mask_slice(&mut cipher, &mut curl);
+            tag(&mut cipher_tag, &mut curl);
+            curl.reset();
+
+            let mut plain = cipher.clone();
+            curl.absorb(&key);
+            unmask_slice(&mut plain, &mut curl);
+            if plain != payload || !verify_tag(&cipher_tag, &mut curl) {
+                return false;
+            }
+            curl.reset();
+            if cipher.is_empty() {
+                return true;
+            }
+
+            let pos = pos % cipher.len();
+            cipher[pos] = if cipher[pos] == 1 { -1 } else { cipher[pos] + 1 };
+            curl.absorb(&key);
+            unmask_slice(&mut cipher, &mut curl);
+            !verify_tag(&cipher_tag, &mut curl)
+        }
+    }
 }
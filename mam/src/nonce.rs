@@ -0,0 +1,130 @@
+//! Hamming nonce search with an iteration budget
+//!
+//! Unlike `HammingNonce::search`, a `NonceSearch` can be run in slices of a
+//! given number of iterations and be cancelled between any two of them, so a
+//! publisher can report progress or abort a slow publish.
+use alloc::Vec;
+use curl::*;
+use sign::iss;
+use trytes::*;
+use errors::*;
+use security::*;
+
+/// Length of the hamming nonce of a signed payload
+pub const NONCE_LENGTH: usize = HASH_LENGTH / 3;
+
+/// A search for a nonce which, absorbed into a snapshot of the encryption
+/// sponge, yields a hash of the required security
+pub struct NonceSearch {
+    state: Vec<Trit>,
+    security: Security,
+    nonce: [Trit; NONCE_LENGTH],
+    iterations: usize,
+    found: bool,
+}
+
+fn increment(trits: &mut [Trit]) {
+    for t in trits.iter_mut() {
+        *t += 1;
+        if *t > 1 {
+            *t = -1;
+        } else {
+            break;
+        }
+    }
+}
+
+impl NonceSearch {
+    /// Prepares a search from the sponge of `curl`, which must have absorbed
+    /// everything preceding the nonce
+    pub fn new<C: Curl<Trit>>(security: Security, curl: &C) -> Self {
+        NonceSearch {
+            state: curl.state().to_vec(),
+            security: security,
+            nonce: [0; NONCE_LENGTH],
+            iterations: 0,
+            found: false,
+        }
+    }
+
+    pub fn security(&self) -> Security {
+        self.security
+    }
+
+    /// Number of nonces tried so far
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// The nonce, once one has been found
+    pub fn nonce(&self) -> Option<&[Trit]> {
+        if self.found { Some(&self.nonce[..]) } else { None }
+    }
+
+    /// Tries at most `budget` more nonces, using `curl` as scratch space.
+    ///
+    /// `cancel` is called with the number of nonces tried so far before each
+    /// one, and aborts the search by returning `true`. The search can be
+    /// resumed by calling `run` again.
+    ///
+    /// Returns the nonce, or `MamError::NonceSearchFailed` if the budget was
+    /// exhausted or the search was cancelled
+    pub fn run<C, F>(&mut self, budget: usize, curl: &mut C, mut cancel: F) -> Result<&[Trit], MamError>
+    where
+        C: Curl<Trit>,
+        F: FnMut(usize) -> bool,
+    {
+        for _ in 0..budget {
+            if self.found || cancel(self.iterations) {
+                break;
+            }
+            curl.state_mut().clone_from_slice(&self.state);
+            curl.absorb(&self.nonce);
+            self.found = iss::checksum_security(curl.rate()) == self.security.level();
+            if !self.found {
+                increment(&mut self.nonce);
+            }
+            self.iterations += 1;
+        }
+        curl.reset();
+        self.nonce().ok_or(MamError::NonceSearchFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+
+    #[test]
+    fn finds_nonce_within_budget() {
+        let message: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let mut curl = CpuCurl::<Trit>::default();
+        curl.absorb(&message);
+        let mut search = NonceSearch::new(Security::Low, &curl);
+        curl.reset();
+
+        assert_eq!(search.run(0, &mut curl, |_| false), Err(MamError::NonceSearchFailed));
+        assert_eq!(search.run(100, &mut curl, |_| true), Err(MamError::NonceSearchFailed));
+        assert_eq!(search.iterations(), 0);
+        assert_eq!(search.nonce(), None);
+
+        let mut progress = 0;
+        let nonce = search
+            .run(usize::max_value(), &mut curl, |i| {
+                progress = i;
+                false
+            })
+            .unwrap()
+            .to_vec();
+        assert_eq!(progress + 1, search.iterations());
+
+        curl.absorb(&message);
+        curl.absorb(&nonce);
+        assert_eq!(iss::checksum_security(curl.rate()), Security::Low.level());
+    }
+}
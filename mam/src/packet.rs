@@ -0,0 +1,210 @@
+//! Packets of several messages under a single signature
+//!
+//! A packet is the message of a `MessageType::Packet` payload, and spends a
+//! single merkle leaf and signature on a burst of messages:
+//!
+//! ```text
+//! [
+//!     Encoded Message Length,
+//!     Message,
+//!     Encoded Message Length,
+//!     Message,
+//!     ...
+//! ]
+//! ```
+use alloc::Vec;
+use curl::*;
+use trytes::*;
+use errors::*;
+use security::*;
+use mam::{self, MessageType, Version};
+use pascal::{Reader, Writer};
+
+/// Length of a packet containing `messages`
+pub fn length(messages: &[&[Trit]]) -> usize {
+    messages.iter().fold(0, |acc, message| {
+        acc + mam::number_length(message.len()) + message.len()
+    })
+}
+
+/// Writes a packet of `messages` to `out`, returning its length
+pub fn write(messages: &[&[Trit]], out: &mut [Trit]) -> usize {
+    let packet_length = length(messages);
+    assert!(
+        out.len() >= packet_length,
+        "should be: {}, is {}",
+        packet_length,
+        out.len()
+    );
+    let mut writer = Writer::new(out);
+    for message in messages {
+        mam::write_number(message.len(), &mut writer);
+        writer.write_trits(message);
+    }
+    writer.position()
+}
+
+/// Iterator over the messages of a packet
+pub struct Messages<'a> {
+    reader: Reader<'a>,
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = Result<&'a [Trit], MamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.remaining() == 0 {
+            return None;
+        }
+        let reader = &mut self.reader;
+        let message = Version::Compact.read(reader).ok().and_then(|message_length| {
+            if message_length > reader.remaining() {
+                None
+            } else {
+                reader.read_trits(message_length).ok()
+            }
+        });
+        if message.is_none() {
+            let remaining = reader.remaining();
+            let _ = reader.read_trits(remaining);
+        }
+        Some(message.ok_or(MamError::ArrayOutOfBounds))
+    }
+}
+
+/// Iterates over the messages of a `packet`, i.e. the message of a parsed
+/// `MessageType::Packet` payload
+pub fn messages(packet: &[Trit]) -> Messages {
+    Messages { reader: Reader::new(packet) }
+}
+
+/// Minimum length of a payload made by `create`
+pub fn min_length(
+    messages: &[&[Trit]],
+    siblings_length: usize,
+    index: usize,
+    security: Security,
+) -> usize {
+    mam::signed_min_length(
+        MessageType::Packet,
+        length(messages),
+        siblings_length,
+        index,
+        security,
+    )
+}
+
+/// Creates a signed, encrypted payload from a packet of `messages`.
+///
+/// Takes the same parameters as `mam::create`.
+///
+/// Returns the length of the payload, or `MamError::NonceSearchFailed` if
+/// no hamming nonce was found
+pub fn create<C, CB, H>(
+    seed: &[Trit],
+    messages: &[&[Trit]],
+    side_key: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next: &[Trit],
+    start: isize,
+    index: usize,
+    security: Security,
+    payload: &mut [Trit],
+    curl: &mut C,
+    encr_curl: &mut C,
+    bcurl: &mut CB,
+) -> Result<usize, MamError>
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+    H: HammingNonce<Trit>,
+{
+    let mut packet: Vec<Trit> = vec![0; length(messages)];
+    write(messages, &mut packet);
+    mam::create_signed::<C, CB, H>(
+        MessageType::Packet,
+        seed,
+        &packet,
+        side_key,
+        root,
+        siblings,
+        next,
+        start,
+        index,
+        security,
+        payload,
+        curl,
+        encr_curl,
+        bcurl,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+    use merkle;
+
+    fn trits(s: &str) -> Vec<Trit> {
+        s.chars().flat_map(char_to_trits).cloned().collect()
+    }
+
+    #[test]
+    fn packet_round_trip() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let side_key = trits("EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD");
+        let first = trits("TEMPERATURE9TWENTYONE");
+        let second = trits("");
+        let third = trits("HUMIDITY9FORTYTWO");
+        let burst: [&[Trit]; 3] = [&first, &second, &third];
+        let security = Security::Low;
+        let start: isize = 0;
+        let index = 0;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let root = merkle::create(&seed, start, 1, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root_trits: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&root, &mut root_trits);
+
+        let mut payload: Vec<Trit> = vec![0; min_length(&burst, 0, index, security)];
+        create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &burst,
+            &side_key,
+            &root_trits,
+            &[],
+            &root_trits,
+            start,
+            index,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+
+        let parsed = mam::parse(&mut payload, &side_key, &root_trits, &mut c1).unwrap();
+        assert_eq!(parsed.kind, MessageType::Packet);
+        let parsed_messages: Vec<&[Trit]> = messages(parsed.message(&payload))
+            .map(|m| m.unwrap())
+            .collect();
+        assert_eq!(&parsed_messages[..], &burst[..]);
+    }
+
+    #[test]
+    fn truncated_packet_fails() {
+        let first = trits("TEMPERATURE9TWENTYONE");
+        let burst: [&[Trit]; 1] = [&first];
+        let mut packet: Vec<Trit> = vec![0; length(&burst)];
+        write(&burst, &mut packet);
+        let len = packet.len();
+        let mut iter = messages(&packet[..len - 1]);
+        assert_eq!(iter.next(), Some(Err(MamError::ArrayOutOfBounds)));
+        assert_eq!(iter.next(), None);
+    }
+}
@@ -0,0 +1,47 @@
+use sign::iss;
+use errors::*;
+
+/// Security level of a channel's one-time signatures, giving the number of
+/// key fragments in each signature
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum Security {
+    Low = 1,
+    Medium = 2,
+    High = 3,
+}
+
+impl Security {
+    /// Validates a numeric security `level`, which must be in `1..=3`
+    pub fn from_level(level: usize) -> Result<Security, MamError> {
+        match level {
+            1 => Ok(Security::Low),
+            2 => Ok(Security::Medium),
+            3 => Ok(Security::High),
+            _ => Err(MamError::InvalidSecurity),
+        }
+    }
+
+    pub fn level(self) -> usize {
+        self as usize
+    }
+
+    /// Length of a signature at this security level
+    pub fn signature_length(self) -> usize {
+        self.level() * iss::KEY_LENGTH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_levels() {
+        assert_eq!(Security::from_level(0), Err(MamError::InvalidSecurity));
+        assert_eq!(Security::from_level(4), Err(MamError::InvalidSecurity));
+        for level in 1..4 {
+            assert_eq!(Security::from_level(level).unwrap().level(), level);
+        }
+        assert!(Security::Low < Security::Medium && Security::Medium < Security::High);
+    }
+}
@@ -0,0 +1,138 @@
+//! Seeds and derivation of per-channel seeds
+//!
+//! A device publishing several channels only needs to back up one master
+//! seed. Each channel seed is derived deterministically from it as
+//! `H(Master + Kind + Length + Label)`, where `Kind` tells label strings,
+//! channel numbers and subtrees apart, and `Length` is the pascal encoded
+//! label length.
+//! The derived seed is used like any other seed, e.g. with `LeafKey::new`.
+use alloc::Vec;
+use core::ops::Deref;
+use curl::*;
+use trytes::*;
+use errors::*;
+use keys;
+use wipe::*;
+use pascal;
+
+/// Length of a seed
+pub const SEED_LENGTH: usize = HASH_LENGTH;
+
+const LABEL_KIND: [Trit; TRITS_PER_TRYTE] = [1, 0, 0];
+const NUMBER_KIND: [Trit; TRITS_PER_TRYTE] = [-1, 0, 0];
+const SUBTREE_KIND: [Trit; TRITS_PER_TRYTE] = [0, 1, 0];
+
+/// A channel seed, which is wiped from memory when dropped
+pub struct Seed {
+    trits: Vec<Trit>,
+}
+
+impl Seed {
+    /// Copies a seed from `trits`, which the caller should wipe afterwards
+    pub fn new(trits: &[Trit]) -> Result<Seed, MamError> {
+        if trits.len() != SEED_LENGTH || keys::validate(trits).is_err() {
+            return Err(MamError::InvalidSeed);
+        }
+        Ok(Seed { trits: trits.to_vec() })
+    }
+
+    /// Parses a seed from a string of 81 trytes
+    pub fn from_trytes(seed: &str) -> Result<Seed, MamError> {
+        let mut trits = keys::from_trytes(seed).map_err(|_| MamError::InvalidSeed)?;
+        let seed = Seed::new(&trits);
+        wipe(&mut trits);
+        seed
+    }
+
+    pub fn trits(&self) -> &[Trit] {
+        &self.trits
+    }
+
+    /// Derives the seed of the channel named `label`
+    pub fn channel<C: Curl<Trit>>(&self, label: &str, curl: &mut C) -> Seed {
+        let mut label = keys::passphrase_to_trits(label);
+        let seed = self.derive(&LABEL_KIND, &label, curl);
+        wipe(&mut label);
+        seed
+    }
+
+    /// Derives the seed of the channel with the given `number`
+    pub fn channel_number<C: Curl<Trit>>(&self, number: usize, curl: &mut C) -> Seed {
+        let mut label: Vec<Trit> = vec![0; pascal::encoded_length(number as isize)];
+        pascal::encode(number as isize, &mut label);
+        self.derive(&NUMBER_KIND, &label, curl)
+    }
+
+    /// Derives the seed of the subtree certified by the leaf at `top_index`
+    /// of a layered channel's top-level tree, see `layered`
+    pub fn subtree<C: Curl<Trit>>(&self, top_index: usize, curl: &mut C) -> Seed {
+        let mut label: Vec<Trit> = vec![0; pascal::encoded_length(top_index as isize)];
+        pascal::encode(top_index as isize, &mut label);
+        self.derive(&SUBTREE_KIND, &label, curl)
+    }
+
+    fn derive<C: Curl<Trit>>(&self, kind: &[Trit], label: &[Trit], curl: &mut C) -> Seed {
+        let mut length: Vec<Trit> = vec![0; pascal::encoded_length(label.len() as isize)];
+        pascal::encode(label.len() as isize, &mut length);
+        let mut trits: Vec<Trit> = vec![0; SEED_LENGTH];
+        curl.absorb(&self.trits);
+        curl.absorb(kind);
+        curl.absorb(&length);
+        curl.absorb(label);
+        curl.squeeze(&mut trits);
+        wipe_curl(curl);
+        Seed { trits: trits }
+    }
+}
+
+impl Deref for Seed {
+    type Target = [Trit];
+
+    fn deref(&self) -> &[Trit] {
+        &self.trits
+    }
+}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        wipe(&mut self.trits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+
+    #[test]
+    fn validates_seeds() {
+        let seed = Seed::from_trytes(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9",
+        ).unwrap();
+        assert_eq!(seed.len(), SEED_LENGTH);
+        assert!(Seed::from_trytes("ABCDEFGHIJKLMNOPQRSTUVWXYZ9").is_err());
+        assert!(Seed::from_trytes("abc").is_err());
+        assert!(Seed::new(&[2; SEED_LENGTH]).is_err());
+    }
+
+    #[test]
+    fn derives_independent_channels() {
+        let master = Seed::from_trytes(
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9",
+        ).unwrap();
+        let mut curl = CpuCurl::<Trit>::default();
+        let telemetry = master.channel("telemetry", &mut curl);
+        let alerts = master.channel("alerts", &mut curl);
+        assert_eq!(telemetry.len(), SEED_LENGTH);
+        assert!(keys::validate(&telemetry).is_ok());
+        assert_eq!(&telemetry[..], &master.channel("telemetry", &mut curl)[..]);
+        assert!(&telemetry[..] != &alerts[..]);
+        assert!(&telemetry[..] != &master[..]);
+
+        let first = master.channel_number(1, &mut curl);
+        assert_eq!(&first[..], &master.channel_number(1, &mut curl)[..]);
+        assert!(&first[..] != &master.channel_number(2, &mut curl)[..]);
+        assert!(&first[..] != &telemetry[..]);
+        assert!(&first[..] != &master.subtree(1, &mut curl)[..]);
+    }
+}
@@ -0,0 +1,186 @@
+//! Signing abstraction for publishing without the channel seed
+//!
+//! `create_with_signer` only needs a `Signer` to sign the message hash, so
+//! the seed can stay in a separate signing process or a hardware wallet.
+use curl::*;
+use trytes::*;
+use errors::*;
+use leaf::*;
+use security::*;
+
+/// Signs hashes with the one-time keys of a channel's merkle tree
+pub trait Signer {
+    /// Security level of the signatures
+    fn security(&self) -> Security;
+
+    /// Signs `hash` with the key of the leaf at `index`, writing the
+    /// signature of `security().signature_length()` trits to `out`
+    fn sign(&mut self, index: usize, hash: &[Trit], out: &mut [Trit]) -> Result<(), MamError>;
+
+    /// Writes the address of the leaf at `index` to `out`
+    fn address(&mut self, index: usize, out: &mut [Trit]) -> Result<(), MamError>;
+}
+
+/// A `Signer` deriving its keys from a seed held in this process
+pub struct SeedSigner<'a, C: Curl<Trit>> {
+    seed: &'a [Trit],
+    start: isize,
+    security: Security,
+    curl: C,
+}
+
+impl<'a, C: Curl<Trit>> SeedSigner<'a, C> {
+    /// Signs with the keys of the tree beginning at `start` of the `seed`
+    pub fn new(seed: &'a [Trit], start: isize, security: Security, curl: C) -> Self {
+        SeedSigner {
+            seed: seed,
+            start: start,
+            security: security,
+            curl: curl,
+        }
+    }
+}
+
+impl<'a, C: Curl<Trit> + Default> Signer for SeedSigner<'a, C> {
+    fn security(&self) -> Security {
+        self.security
+    }
+
+    fn sign(&mut self, index: usize, hash: &[Trit], out: &mut [Trit]) -> Result<(), MamError> {
+        if out.len() != self.security.signature_length() {
+            return Err(MamError::ArrayOutOfBounds);
+        }
+        LeafKey::new(self.seed, self.start, index, self.security, &mut self.curl)
+            .sign(hash, out, &mut self.curl);
+        Ok(())
+    }
+
+    fn address(&mut self, index: usize, out: &mut [Trit]) -> Result<(), MamError> {
+        let mut address: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        let mut digest_curl = C::default();
+        LeafKey::new(self.seed, self.start, index, self.security, &mut self.curl)
+            .address(&mut address, &mut self.curl, &mut digest_curl);
+        self.curl.absorb(&address);
+        out.clone_from_slice(&self.curl.rate()[..out.len()]);
+        self.curl.reset();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+    use alloc::Vec;
+    use merkle;
+    use mam;
+
+    /// Signs like a remote signer would, and records what it was asked to sign
+    struct MockSigner<'a> {
+        inner: SeedSigner<'a, CpuCurl<Trit>>,
+        signed: Vec<usize>,
+    }
+
+    impl<'a> Signer for MockSigner<'a> {
+        fn security(&self) -> Security {
+            self.inner.security()
+        }
+
+        fn sign(&mut self, index: usize, hash: &[Trit], out: &mut [Trit]) -> Result<(), MamError> {
+            self.signed.push(index);
+            self.inner.sign(index, hash, out)
+        }
+
+        fn address(&mut self, index: usize, out: &mut [Trit]) -> Result<(), MamError> {
+            self.inner.address(index, out)
+        }
+    }
+
+    #[test]
+    fn creates_with_signer() {
+        let seed: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let message: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let side_key: Vec<Trit> = "EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let security = Security::Low;
+        let start: isize = 2;
+        let count: usize = 4;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let root = merkle::create(&seed, start, count, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root_trits: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&root, &mut root_trits);
+
+        let mut signer = MockSigner {
+            inner: SeedSigner::new(&seed, start, security, CpuCurl::<Trit>::default()),
+            signed: Vec::new(),
+        };
+
+        for index in 0..count {
+            let branch = merkle::branch(&root, index);
+            let siblings_length = merkle::len(&branch) * HASH_LENGTH;
+            let mut siblings: Vec<Trit> = vec![0; siblings_length];
+            merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
+
+            let mut payload: Vec<Trit> =
+                vec![0; mam::min_length(message.len(), siblings.len(), index, security)];
+            mam::create_with_signer::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam, _>(
+                &mut signer,
+                &message,
+                &side_key,
+                &root_trits,
+                &siblings,
+                &root_trits,
+                index,
+                &mut payload,
+                &mut c1,
+                &mut c2,
+                &mut bc,
+            ).unwrap();
+
+            let parsed = mam::parse(&mut payload, &side_key, &root_trits, &mut c1).unwrap();
+            assert_eq!(parsed.message(&payload), &message[..]);
+        }
+        assert_eq!(signer.signed, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn address_matches_leaf() {
+        let seed: Vec<Trit> = "TX9XRR9SRCOBMTYDTMKNEIJCSZIMEUPWCNLC9DPDZKKAEMEFVSTEVUFTRUZXEHLULEIYJIEOWIC9STAHW"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let security = Security::Medium;
+        let start: isize = 5;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+
+        // the root of a single leaf tree is the address of that leaf
+        let root = merkle::create(&seed, start + 1, 1, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root_trits: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&root, &mut root_trits);
+
+        let mut signer = SeedSigner::new(&seed, start, security, c1);
+        let mut address: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        signer.address(1, &mut address).unwrap();
+        assert_eq!(&address[..], &root_trits[..]);
+    }
+}
@@ -0,0 +1,303 @@
+//! Bit-sliced masking of many independent payloads at once
+//!
+//! A `BCTrit` holds the same trit of up to `LANES` sponges, one per bit, so
+//! a single `Curl<BCTrit>` transform advances the sponges of `LANES`
+//! payloads together. Payloads are given with a `Curl<Trit>` each, and come
+//! out masked or unmasked, with their curl left in the same state, as if
+//! they had gone through the scalar functions of `mask` one by one.
+use core::cmp;
+use alloc::Vec;
+use curl::*;
+use trytes::*;
+use tmath::*;
+
+/// Number of payloads masked at once, one per bit of a `BCTrit`
+#[cfg(target_pointer_width = "64")]
+pub const LANES: usize = 64;
+/// Number of payloads masked at once, one per bit of a `BCTrit`
+#[cfg(target_pointer_width = "32")]
+pub const LANES: usize = 32;
+
+fn set(bct: &mut BCTrit, lane: usize, trit: Trit) {
+    let bit = 1 << lane;
+    let (low, high) = match trit {
+        1 => (0, bit),
+        -1 => (bit, 0),
+        _ => (bit, bit),
+    };
+    bct.0 = (bct.0 & !bit) | low;
+    bct.1 = (bct.1 & !bit) | high;
+}
+
+fn get(bct: &BCTrit, lane: usize) -> Trit {
+    match ((bct.0 >> lane) & 1, (bct.1 >> lane) & 1) {
+        (0, 1) => 1,
+        (1, 0) => -1,
+        _ => 0,
+    }
+}
+
+fn load<C, CB>(curl: &C, lane: usize, bcurl: &mut CB)
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+{
+    for (s, &t) in bcurl.state_mut().iter_mut().zip(curl.state()) {
+        set(s, lane, t);
+    }
+}
+
+fn store<C, CB>(bcurl: &CB, lane: usize, curl: &mut C)
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+{
+    for (t, s) in curl.state_mut().iter_mut().zip(bcurl.state()) {
+        *t = get(s, lane);
+    }
+}
+
+/// Masks or unmasks up to `LANES` payloads, one per lane. The first chunk of
+/// each payload is masked with its key in `first_keys`, if given, and with
+/// the rate of its curl otherwise, like every later chunk.
+fn run<C, CB>(
+    payloads: &mut [&mut [Trit]],
+    first_keys: Option<&[Vec<Trit>]>,
+    masking: bool,
+    curls: &mut [C],
+    bcurl: &mut CB,
+) where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+{
+    bcurl.reset();
+    for (lane, curl) in curls.iter().enumerate() {
+        load(curl, lane, bcurl);
+    }
+    let longest = payloads.iter().map(|p| p.len()).max().unwrap_or(0);
+    let mut rate: [BCTrit; HASH_LENGTH] = [(0, 0); HASH_LENGTH];
+    let mut offset = 0;
+    while offset < longest {
+        rate.clone_from_slice(bcurl.rate());
+        for (lane, payload) in payloads.iter_mut().enumerate() {
+            if offset >= payload.len() {
+                continue;
+            }
+            let end = cmp::min(offset + HASH_LENGTH, payload.len());
+            for (i, t) in payload[offset..end].iter_mut().enumerate() {
+                let key = match first_keys {
+                    Some(keys) if offset == 0 => keys[lane][i],
+                    _ => get(&rate[i], lane),
+                };
+                let plain = if masking { *t } else { trit_sum(*t, -key) };
+                *t = if masking { trit_sum(plain, key) } else { plain };
+                set(&mut rate[i], lane, plain);
+            }
+        }
+        // Trits past the end of a lane's chunk are absorbed unchanged, as a
+        // shorter absorb would leave them
+        bcurl.absorb(&rate);
+        for (lane, payload) in payloads.iter().enumerate() {
+            if payload.len() > offset && payload.len() <= offset + HASH_LENGTH {
+                store(bcurl, lane, &mut curls[lane]);
+            }
+        }
+        offset += HASH_LENGTH;
+    }
+    bcurl.reset();
+}
+
+/// Squeezes the first key chunk of `mask::mask` and `mask::unmask` for each
+/// of `keys`
+fn first_keys<C: Curl<Trit>>(keys: &[&[Trit]], curls: &mut [C]) -> Vec<Vec<Trit>> {
+    keys.iter()
+        .zip(curls.iter_mut())
+        .map(|(key, curl)| {
+            curl.absorb(key);
+            let mut first: Vec<Trit> = vec![0; HASH_LENGTH];
+            curl.squeeze(&mut first);
+            first
+        })
+        .collect()
+}
+
+/// Masks each of `payloads` like `mask::mask_slice` with the curl of the
+/// same index in `curls`
+pub fn mask_slices<C, CB>(payloads: &mut [&mut [Trit]], curls: &mut [C], bcurl: &mut CB)
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+{
+    assert_eq!(payloads.len(), curls.len());
+    for (payloads, curls) in payloads.chunks_mut(LANES).zip(curls.chunks_mut(LANES)) {
+        run(payloads, None, true, curls, bcurl);
+    }
+}
+
+/// Unmasks each of `payloads` like `mask::unmask_slice` with the curl of the
+/// same index in `curls`
+pub fn unmask_slices<C, CB>(payloads: &mut [&mut [Trit]], curls: &mut [C], bcurl: &mut CB)
+where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+{
+    assert_eq!(payloads.len(), curls.len());
+    for (payloads, curls) in payloads.chunks_mut(LANES).zip(curls.chunks_mut(LANES)) {
+        run(payloads, None, false, curls, bcurl);
+    }
+}
+
+/// Masks each of `payloads` like `mask::mask` with the key and curl of the
+/// same index in `keys` and `curls`
+pub fn mask_many<C, CB>(
+    payloads: &mut [&mut [Trit]],
+    keys: &[&[Trit]],
+    curls: &mut [C],
+    bcurl: &mut CB,
+) where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+{
+    assert_eq!(payloads.len(), curls.len());
+    assert_eq!(keys.len(), curls.len());
+    for ((payloads, keys), curls) in payloads
+        .chunks_mut(LANES)
+        .zip(keys.chunks(LANES))
+        .zip(curls.chunks_mut(LANES))
+    {
+        let first = first_keys(keys, curls);
+        run(payloads, Some(&first[..]), true, curls, bcurl);
+    }
+}
+
+/// Unmasks each of `payloads` like `mask::unmask` with the key and curl of
+/// the same index in `keys` and `curls`
+pub fn unmask_many<C, CB>(
+    payloads: &mut [&mut [Trit]],
+    keys: &[&[Trit]],
+    curls: &mut [C],
+    bcurl: &mut CB,
+) where
+    C: Curl<Trit>,
+    CB: Curl<BCTrit>,
+{
+    assert_eq!(payloads.len(), curls.len());
+    assert_eq!(keys.len(), curls.len());
+    for ((payloads, keys), curls) in payloads
+        .chunks_mut(LANES)
+        .zip(keys.chunks(LANES))
+        .zip(curls.chunks_mut(LANES))
+    {
+        let first = first_keys(keys, curls);
+        run(payloads, Some(&first[..]), false, curls, bcurl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+    use mask::*;
+
+    const LENGTHS: [usize; 8] = [0, 1, 100, 242, 243, 244, 500, 729];
+
+    /// Payloads of varying lengths, more than fit in the lanes of one
+    /// `BCTrit`, with a different key each
+    fn payloads() -> (Vec<Vec<Trit>>, Vec<Vec<Trit>>) {
+        let message: Vec<Trit> = "AMESSAGEFORYOU9".chars().flat_map(char_to_trits).cloned().collect();
+        let payloads: Vec<Vec<Trit>> = (0..LANES + 6)
+            .map(|i| {
+                message
+                    .iter()
+                    .cycle()
+                    .skip(i)
+                    .take(LENGTHS[i % LENGTHS.len()])
+                    .cloned()
+                    .collect::<Vec<Trit>>()
+            })
+            .collect();
+        let keys: Vec<Vec<Trit>> = (0..LANES + 6)
+            .map(|i| {
+                let mut key: Vec<Trit> = "MYSIDEKEY".chars().flat_map(char_to_trits).cloned().collect();
+                add_assign(&mut key, i as isize);
+                key
+            })
+            .collect();
+        (payloads, keys)
+    }
+
+    fn states(curls: &[CpuCurl<Trit>]) -> Vec<Vec<Trit>> {
+        curls.iter().map(|c| c.state().to_vec()).collect()
+    }
+
+    #[test]
+    fn slices_match_scalar_path() {
+        let (plain, keys) = payloads();
+        let mut bcurl = CpuCurl::<BCTrit>::default();
+
+        let mut expected = plain.clone();
+        let mut expected_curls: Vec<CpuCurl<Trit>> = Vec::new();
+        for (payload, key) in expected.iter_mut().zip(keys.iter()) {
+            let mut curl = CpuCurl::<Trit>::default();
+            curl.absorb(key);
+            mask_slice(payload, &mut curl);
+            expected_curls.push(curl);
+        }
+
+        let mut actual = plain.clone();
+        let mut curls: Vec<CpuCurl<Trit>> = keys.iter()
+            .map(|key| {
+                let mut curl = CpuCurl::<Trit>::default();
+                curl.absorb(key);
+                curl
+            })
+            .collect();
+        {
+            let mut slices: Vec<&mut [Trit]> = actual.iter_mut().map(|p| &mut p[..]).collect();
+            mask_slices(&mut slices, &mut curls, &mut bcurl);
+        }
+        assert_eq!(actual, expected);
+        assert_eq!(states(&curls), states(&expected_curls));
+
+        for (curl, key) in curls.iter_mut().zip(keys.iter()) {
+            curl.reset();
+            curl.absorb(key);
+        }
+        {
+            let mut slices: Vec<&mut [Trit]> = actual.iter_mut().map(|p| &mut p[..]).collect();
+            unmask_slices(&mut slices, &mut curls, &mut bcurl);
+        }
+        assert_eq!(actual, plain);
+        assert_eq!(states(&curls), states(&expected_curls));
+    }
+
+    #[test]
+    fn keyed_masking_matches_scalar_path() {
+        let (plain, keys) = payloads();
+        let key_slices: Vec<&[Trit]> = keys.iter().map(|k| &k[..]).collect();
+        let mut bcurl = CpuCurl::<BCTrit>::default();
+
+        let mut expected = plain.clone();
+        for (payload, key) in expected.iter_mut().zip(keys.iter()) {
+            let mut curl = CpuCurl::<Trit>::default();
+            mask(payload, key, &mut curl);
+        }
+
+        let mut actual = plain.clone();
+        let mut curls: Vec<CpuCurl<Trit>> = keys.iter().map(|_| CpuCurl::default()).collect();
+        {
+            let mut slices: Vec<&mut [Trit]> = actual.iter_mut().map(|p| &mut p[..]).collect();
+            mask_many(&mut slices, &key_slices, &mut curls, &mut bcurl);
+        }
+        assert_eq!(actual, expected);
+
+        for curl in curls.iter_mut() {
+            curl.reset();
+        }
+        {
+            let mut slices: Vec<&mut [Trit]> = actual.iter_mut().map(|p| &mut p[..]).collect();
+            unmask_many(&mut slices, &key_slices, &mut curls, &mut bcurl);
+        }
+        assert_eq!(actual, plain);
+    }
+}
@@ -0,0 +1,408 @@
+//! State of a subscriber following a channel
+use alloc::Vec;
+use curl::*;
+use trytes::*;
+use errors::*;
+use mam::{self, Parsed};
+use fork;
+use control;
+
+/// Whether a subscriber still follows its channel, or why it stopped
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum State {
+    Following,
+    /// The channel was closed for the given reason
+    Ended(Vec<Trit>),
+    /// The current or next root was revoked for the given reason
+    Revoked { root: Vec<Trit>, reason: Vec<Trit> },
+}
+
+/// Follows a channel from its `root`, remembering the next root and the
+/// child channels announced on it
+pub struct Subscriber {
+    side_key: Vec<Trit>,
+    root: Vec<Trit>,
+    next: Option<Vec<Trit>>,
+    children: Vec<Vec<Trit>>,
+    state: State,
+}
+
+impl Subscriber {
+    pub fn new(side_key: &[Trit], root: &[Trit]) -> Self {
+        Subscriber {
+            side_key: side_key.to_vec(),
+            root: root.to_vec(),
+            next: None,
+            children: Vec::new(),
+            state: State::Following,
+        }
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// The root payloads are currently parsed with
+    pub fn root(&self) -> &[Trit] {
+        &self.root
+    }
+
+    /// The next root announced by the latest message, if any
+    pub fn next(&self) -> Option<&[Trit]> {
+        self.next.as_ref().map(|next| &next[..])
+    }
+
+    /// Roots of the child channels announced so far, in order
+    pub fn children(&self) -> &[Vec<Trit>] {
+        &self.children
+    }
+
+    /// Parses a `payload` published under the current root, remembering its
+    /// next root, and the child root if it is a fork announcement.
+    ///
+    /// Unsigned payloads are only authenticated by the side key, which every
+    /// subscriber holds, so they are returned without changing any of this.
+    ///
+    /// End-of-channel payloads and revocations of the current root, or of the
+    /// next root announced before, stop the subscriber, after which
+    /// `MamError::ChannelClosed` is returned. Revocations of a child channel
+    /// forget that child. Neither changes the next root.
+    pub fn receive<C: Curl<Trit>>(
+        &mut self,
+        payload: &mut [Trit],
+        curl: &mut C,
+    ) -> Result<Parsed, MamError> {
+        if self.state != State::Following {
+            return Err(MamError::ChannelClosed);
+        }
+        let parsed = mam::parse(payload, &self.side_key, &self.root, curl)?;
+        if parsed.security.is_none() {
+            return Ok(parsed);
+        }
+        if let Some(reason) = control::reason(&parsed, payload) {
+            match control::revoked(&parsed, payload) {
+                Some(revoked) => {
+                    self.children.retain(|c| &c[..] != revoked);
+                    if &self.root[..] == revoked || self.next() == Some(revoked) {
+                        self.state = State::Revoked {
+                            root: revoked.to_vec(),
+                            reason: reason.to_vec(),
+                        };
+                    }
+                }
+                None => self.state = State::Ended(reason.to_vec()),
+            }
+            return Ok(parsed);
+        }
+        self.next = Some(parsed.next(payload).to_vec());
+        if let Some(child) = fork::child(&parsed, payload) {
+            if !self.children.iter().any(|c| &c[..] == child) {
+                self.children.push(child.to_vec());
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Moves on to the next root, e.g. once the current tree is used up,
+    /// returning whether one was known and the channel is still followed
+    pub fn advance(&mut self) -> bool {
+        if self.state != State::Following {
+            return false;
+        }
+        match self.next.take() {
+            Some(next) => {
+                self.root = next;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// A subscriber of the announced child channel at `child`, sharing the
+    /// side key of this channel
+    pub fn follow(&self, child: usize) -> Option<Subscriber> {
+        self.children.get(child).map(|root| {
+            Subscriber::new(&self.side_key, root)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+    use merkle;
+    use security::*;
+
+    fn trits(s: &str) -> Vec<Trit> {
+        s.chars().flat_map(char_to_trits).cloned().collect()
+    }
+
+    #[test]
+    fn follows_fork_announcements() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let side_key = trits("EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD");
+        let message = trits("CUSTOMER9ONE");
+        let security = Security::Low;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let mut roots: [[Trit; HASH_LENGTH]; 3] = [[0; HASH_LENGTH]; 3];
+        for (start, root) in roots.iter_mut().enumerate() {
+            let tree = merkle::create(
+                &seed,
+                start as isize,
+                1,
+                security.level(),
+                &mut c1,
+                &mut c2,
+                &mut c3,
+            );
+            merkle::slice(&tree, root);
+        }
+        let (root, next, child) = (roots[0], roots[1], roots[2]);
+
+        let mut payload: Vec<Trit> = vec![0; fork::min_length(message.len(), 0, 0, security)];
+        fork::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &child,
+            &message,
+            &side_key,
+            &root,
+            &[],
+            &next,
+            0,
+            0,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+
+        let mut subscriber = Subscriber::new(&side_key, &root);
+        let parsed = subscriber.receive(&mut payload, &mut c1).unwrap();
+        assert_eq!(fork::message(&parsed, &payload), Some(&message[..]));
+        assert_eq!(subscriber.children(), &[child.to_vec()][..]);
+        assert_eq!(subscriber.follow(0).unwrap().root(), &child[..]);
+        assert!(subscriber.follow(1).is_none());
+
+        assert!(subscriber.advance());
+        assert_eq!(subscriber.root(), &next[..]);
+        assert!(!subscriber.advance());
+    }
+
+    #[test]
+    fn stops_on_end_and_revocation() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let side_key = trits("EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD");
+        let reason = trits("KEYS9LEAKED");
+        let security = Security::Low;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let tree = merkle::create(&seed, 0, 1, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&tree, &mut root);
+
+        let mut payload: Vec<Trit> =
+            vec![0; control::revocation_min_length(reason.len(), 0, 0, security)];
+        control::create_revocation::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &root,
+            &reason,
+            &side_key,
+            &root,
+            &[],
+            &root,
+            0,
+            0,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+        let mut revoked = payload.clone();
+        let mut subscriber = Subscriber::new(&side_key, &root);
+        subscriber.receive(&mut revoked, &mut c1).unwrap();
+        assert_eq!(
+            subscriber.state(),
+            &State::Revoked {
+                root: root.to_vec(),
+                reason: reason.clone(),
+            }
+        );
+        assert_eq!(
+            subscriber.receive(&mut payload, &mut c1),
+            Err(MamError::ChannelClosed)
+        );
+        assert!(!subscriber.advance());
+
+        let mut payload: Vec<Trit> = vec![0; control::end_min_length(reason.len(), 0, 0, security)];
+        control::create_end::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &reason,
+            &side_key,
+            &root,
+            &[],
+            &root,
+            0,
+            0,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+        let mut subscriber = Subscriber::new(&side_key, &root);
+        subscriber.receive(&mut payload, &mut c1).unwrap();
+        assert_eq!(subscriber.state(), &State::Ended(reason));
+    }
+
+    #[test]
+    fn ignores_unsigned_announcements() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let side_key = trits("EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD");
+        let message = trits("FOLLOW9ME");
+        let security = Security::Low;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let tree = merkle::create(&seed, 0, 1, security.level(), &mut c1, &mut c2, &mut c3);
+        let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        merkle::slice(&tree, &mut root);
+        let next = trits("NEXT9ROOT");
+        let mut next_root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        next_root[..next.len()].clone_from_slice(&next);
+
+        let mut payload: Vec<Trit> = vec![0; fork::min_length(message.len(), 0, 0, security)];
+        fork::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &root,
+            &message,
+            &side_key,
+            &root,
+            &[],
+            &next_root,
+            0,
+            0,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+        let mut subscriber = Subscriber::new(&side_key, &root);
+        subscriber.receive(&mut payload, &mut c1).unwrap();
+
+        // Anyone holding the side key can make an unsigned payload pointing
+        // to a root of their own
+        let forged_root = trits("FORGED9ROOT");
+        let mut forged_next: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+        forged_next[..forged_root.len()].clone_from_slice(&forged_root);
+        let mut forged: Vec<Trit> = vec![0; mam::unsigned_min_length(HASH_LENGTH, 1)];
+        mam::create_unsigned(
+            &forged_next,
+            &side_key,
+            &root,
+            &forged_next,
+            1,
+            &mut forged,
+            &mut c1,
+        );
+        let parsed = subscriber.receive(&mut forged, &mut c1).unwrap();
+        assert_eq!(parsed.security, None);
+        assert_eq!(subscriber.next(), Some(&next_root[..]));
+        assert_eq!(subscriber.children(), &[root.to_vec()][..]);
+        assert_eq!(subscriber.state(), &State::Following);
+    }
+
+    #[test]
+    fn stops_on_revocation_of_announced_next_root() {
+        let seed = trits("ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9");
+        let side_key = trits("EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD");
+        let message = trits("MOVING9ON");
+        let reason = trits("NEXT9KEYS9LEAKED");
+        let security = Security::Low;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        let mut roots: [[Trit; HASH_LENGTH]; 2] = [[0; HASH_LENGTH]; 2];
+        for (start, root) in roots.iter_mut().enumerate() {
+            let tree = merkle::create(
+                &seed,
+                start as isize,
+                1,
+                security.level(),
+                &mut c1,
+                &mut c2,
+                &mut c3,
+            );
+            merkle::slice(&tree, root);
+        }
+        let (root, next) = (roots[0], roots[1]);
+
+        let mut payload: Vec<Trit> = vec![0; mam::min_length(message.len(), 0, 0, security)];
+        mam::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &message,
+            &side_key,
+            &root,
+            &[],
+            &next,
+            0,
+            0,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+        let mut subscriber = Subscriber::new(&side_key, &root);
+        subscriber.receive(&mut payload, &mut c1).unwrap();
+        assert_eq!(subscriber.next(), Some(&next[..]));
+
+        // The revocation announces the current root as its own next root,
+        // which must not hide the revoked one
+        let mut payload: Vec<Trit> =
+            vec![0; control::revocation_min_length(reason.len(), 0, 0, security)];
+        control::create_revocation::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+            &seed,
+            &next,
+            &reason,
+            &side_key,
+            &root,
+            &[],
+            &root,
+            0,
+            0,
+            security,
+            &mut payload,
+            &mut c1,
+            &mut c2,
+            &mut bc,
+        ).unwrap();
+        subscriber.receive(&mut payload, &mut c1).unwrap();
+        assert_eq!(
+            subscriber.state(),
+            &State::Revoked {
+                root: next.to_vec(),
+                reason: reason,
+            }
+        );
+        assert_eq!(subscriber.next(), Some(&next[..]));
+    }
+}
@@ -0,0 +1,212 @@
+//! Multi-threaded hamming nonce search
+//!
+//! Partitions the nonce space by fixing the last trits of the nonce to a
+//! different value for each worker thread. Every worker searches its
+//! partition with its own binary coded trits Curl, and the first nonce found
+//! is used.
+use alloc::Vec;
+use core::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use curl::*;
+use curl_cpu::*;
+use trytes::*;
+
+/// Number of nonce trits fixed per worker
+const PARTITION_LENGTH: usize = 4;
+/// Number of nonce trits searched between two checks of whether another
+/// worker has found a nonce
+const CHUNK_LENGTH: usize = 13;
+/// Number of workers used by `ThreadedHam`
+pub const DEFAULT_WORKERS: usize = 4;
+/// Largest number of workers the nonce space can be partitioned between
+pub const MAX_WORKERS: usize = 81;
+
+/// Hamming nonce search spread over `DEFAULT_WORKERS` threads
+pub struct ThreadedHam;
+
+impl HammingNonce<Trit> for ThreadedHam {
+    fn search<C, CB>(
+        security: u8,
+        offset: usize,
+        length: usize,
+        tcurl: &mut C,
+        _bcurl: &mut CB,
+    ) -> Option<usize>
+    where
+        C: Curl<Trit>,
+        CB: Curl<BCTrit>,
+    {
+        search_with(DEFAULT_WORKERS, security, offset, length, tcurl)
+    }
+}
+
+/// Increments `trits`, returning `false` once they wrap around to zero
+fn increment(trits: &mut [Trit]) -> bool {
+    for t in trits.iter_mut() {
+        *t += 1;
+        if *t > 1 {
+            *t = -1;
+        } else {
+            break;
+        }
+    }
+    trits.iter().any(|&t| t != 0)
+}
+
+fn write_partition(worker: usize, out: &mut [Trit]) {
+    for t in out.iter_mut() {
+        *t = 0;
+    }
+    for _ in 0..worker {
+        increment(out);
+    }
+}
+
+/// Searches for a hamming nonce of the given `security` in the `length` trits
+/// at `offset` of the state of `tcurl`, using `workers` threads.
+///
+/// On success, leaves the nonce in the state of `tcurl`, as `HammingNonce`
+/// implementations do. Workers search their partition in chunks of
+/// `CHUNK_LENGTH` trits, and stop after the current chunk once a nonce is
+/// found. All workers have stopped when this returns.
+///
+/// Returns `None` if no nonce was found, or if `length` is too short to be
+/// partitioned.
+pub fn search_with<C: Curl<Trit>>(
+    workers: usize,
+    security: u8,
+    offset: usize,
+    length: usize,
+    tcurl: &mut C,
+) -> Option<usize> {
+    assert!(
+        workers > 0 && workers <= MAX_WORKERS,
+        "workers should be in 1..{}, is {}",
+        MAX_WORKERS + 1,
+        workers
+    );
+    if length <= PARTITION_LENGTH {
+        return None;
+    }
+    let chunk_length = cmp::min(CHUNK_LENGTH, length - PARTITION_LENGTH);
+    let chunks_start = offset + chunk_length;
+    let partition_start = offset + length - PARTITION_LENGTH;
+    let done = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+
+    let mut handles = Vec::with_capacity(workers);
+    for worker in 0..workers {
+        let mut state: Vec<Trit> = tcurl.state().to_vec();
+        write_partition(worker, &mut state[partition_start..offset + length]);
+        let tx = tx.clone();
+        let done = done.clone();
+        handles.push(thread::spawn(move || {
+            let mut curl = CpuCurl::<Trit>::default();
+            let mut bcurl = CpuCurl::<BCTrit>::default();
+            let found = loop {
+                curl.state_mut().clone_from_slice(&state);
+                let found = CpuHam::search(security, offset, chunk_length, &mut curl, &mut bcurl);
+                if found.is_some() || done.load(Ordering::SeqCst) ||
+                    !increment(&mut state[chunks_start..partition_start])
+                {
+                    break found;
+                }
+            };
+            let _ = tx.send(found.map(|n| (n, curl.state().to_vec())));
+        }));
+    }
+    drop(tx);
+
+    let mut result = None;
+    for found in rx.iter() {
+        if let Some((n, state)) = found {
+            tcurl.state_mut().clone_from_slice(&state);
+            result = Some(n);
+            break;
+        }
+    }
+    done.store(true, Ordering::SeqCst);
+    for handle in handles {
+        let _ = handle.join();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use security::*;
+    use merkle;
+    use mam;
+
+    #[test]
+    fn short_nonces_are_not_found() {
+        let mut curl = CpuCurl::<Trit>::default();
+        assert_eq!(search_with(1, 1, 0, PARTITION_LENGTH, &mut curl), None);
+        assert_eq!(search_with(MAX_WORKERS, 1, 0, 0, &mut curl), None);
+    }
+
+    #[test]
+    fn parse_accepts_threaded_nonces() {
+        let seed: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let message: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let side_key: Vec<Trit> = "EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD"
+            .chars()
+            .flat_map(char_to_trits)
+            .cloned()
+            .collect();
+        let start: isize = 0;
+        let count: usize = 2;
+
+        let mut c1 = CpuCurl::<Trit>::default();
+        let mut c2 = CpuCurl::<Trit>::default();
+        let mut c3 = CpuCurl::<Trit>::default();
+        let mut bc = CpuCurl::<BCTrit>::default();
+
+        for security in [Security::Low, Security::Medium, Security::High].iter().cloned() {
+            let root = merkle::create(&seed, start, count, security.level(), &mut c1, &mut c2, &mut c3);
+            let mut root_trits: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+            merkle::slice(&root, &mut root_trits);
+
+            for index in 0..count {
+                let branch = merkle::branch(&root, index);
+                let siblings_length = merkle::len(&branch) * HASH_LENGTH;
+                let mut siblings: Vec<Trit> = vec![0; siblings_length];
+                merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
+
+                let mut payload: Vec<Trit> =
+                    vec![0; mam::min_length(message.len(), siblings.len(), index, security)];
+                mam::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, ThreadedHam>(
+                    &seed,
+                    &message,
+                    &side_key,
+                    &root_trits,
+                    &siblings,
+                    &root_trits,
+                    start,
+                    index,
+                    security,
+                    &mut payload,
+                    &mut c1,
+                    &mut c2,
+                    &mut bc,
+                ).unwrap();
+
+                let parsed = mam::parse(&mut payload, &side_key, &root_trits, &mut c1).unwrap();
+                assert_eq!(parsed.security, Some(security));
+                assert_eq!(parsed.message(&payload), &message[..]);
+            }
+        }
+    }
+}
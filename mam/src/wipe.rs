@@ -0,0 +1,41 @@
+//! Wiping of secret-bearing buffers
+//!
+//! Uses volatile writes, so that clearing a buffer which is never read again
+//! is not optimized away.
+use core::ptr;
+use core::sync::atomic::{self, Ordering};
+use curl::*;
+use trytes::*;
+
+/// Overwrites `trits` with zeros
+pub fn wipe(trits: &mut [Trit]) {
+    for t in trits.iter_mut() {
+        unsafe { ptr::write_volatile(t, 0) };
+    }
+    atomic::compiler_fence(Ordering::SeqCst);
+}
+
+/// Wipes the whole state of `curl`, which leaves it reset
+pub fn wipe_curl<C: Curl<Trit>>(curl: &mut C) {
+    wipe(curl.state_mut());
+    curl.reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curl_cpu::*;
+    use alloc::Vec;
+
+    #[test]
+    fn wipes_trits_and_curl() {
+        let mut trits: Vec<Trit> = "MYSECRETKEY".chars().flat_map(char_to_trits).cloned().collect();
+        wipe(&mut trits);
+        assert!(trits.iter().all(|&t| t == 0));
+
+        let mut curl = CpuCurl::<Trit>::default();
+        curl.absorb(&[1; HASH_LENGTH]);
+        wipe_curl(&mut curl);
+        assert!(curl.state().iter().all(|&t| t == 0));
+    }
+}
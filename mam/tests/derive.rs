@@ -0,0 +1,73 @@
+extern crate iota_mam as mam;
+extern crate iota_merkle as merkle;
+extern crate iota_trytes as trytes;
+extern crate iota_curl_cpu as curl_cpu;
+extern crate iota_pascal;
+#[macro_use]
+extern crate iota_pascal_derive;
+
+use curl_cpu::*;
+use trytes::*;
+use iota_pascal::{from_trits, to_trits};
+use mam::Security;
+
+#[derive(TritEncode, TritDecode, PartialEq, Debug)]
+struct Reading {
+    sensor: u32,
+    celsius: i16,
+    label: String,
+    samples: Vec<i64>,
+}
+
+#[test]
+fn derived_messages_round_trip() {
+    let seed: Vec<Trit> = "ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9"
+        .chars()
+        .flat_map(char_to_trits)
+        .cloned()
+        .collect();
+    let side_key: Vec<Trit> = "EFGHIJKLMNOPQRSTUVWXYZ9ABCDEFGHIJKLMNOPQRSTUVWXYZ9ABCDABCD"
+        .chars()
+        .flat_map(char_to_trits)
+        .cloned()
+        .collect();
+    let reading = Reading {
+        sensor: 7,
+        celsius: -21,
+        label: String::from("greenhouse"),
+        samples: vec![1, -2, 3],
+    };
+    let message = to_trits(&reading);
+    let security = Security::Low;
+    let start: isize = 0;
+    let index: usize = 0;
+
+    let mut c1 = CpuCurl::<Trit>::default();
+    let mut c2 = CpuCurl::<Trit>::default();
+    let mut c3 = CpuCurl::<Trit>::default();
+    let mut bc = CpuCurl::<BCTrit>::default();
+
+    let tree = merkle::create(&seed, start, 1, security.level(), &mut c1, &mut c2, &mut c3);
+    let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+    merkle::slice(&tree, &mut root);
+
+    let mut payload: Vec<Trit> = vec![0; mam::min_length(message.len(), 0, index, security)];
+    mam::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+        &seed,
+        &message,
+        &side_key,
+        &root,
+        &[],
+        &root,
+        start,
+        index,
+        security,
+        &mut payload,
+        &mut c1,
+        &mut c2,
+        &mut bc,
+    ).unwrap();
+
+    let parsed = mam::parse(&mut payload, &side_key, &root, &mut c1).unwrap();
+    assert_eq!(from_trits::<Reading>(parsed.message(&payload)), Ok(reading));
+}
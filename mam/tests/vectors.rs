@@ -0,0 +1,74 @@
+extern crate iota_mam as mam;
+extern crate iota_merkle as merkle;
+extern crate iota_trytes as trytes;
+extern crate iota_curl as curl;
+extern crate iota_curl_cpu as curl_cpu;
+extern crate iota_sign as sign;
+extern crate iota_pascal as pascal;
+
+#[path = "../vectors/format.rs"]
+mod format;
+
+use format::Vector;
+
+/// Parses the stored payload of a vector and compares the outcome with the
+/// stored expectations. Payloads are never recreated here, so that the
+/// vectors pin down the format itself.
+fn check(vector: &Vector) {
+    let name = vector.name();
+    let field = |key: &str| {
+        vector.get(key).unwrap_or_else(|| {
+            panic!("{}: missing `{}`, run the generate_vectors example", name, key)
+        })
+    };
+    let parsed = format::parse(field("side_key"), field("root"), field("payload"));
+    assert_eq!(parsed.result, field("result"), "result of {}", name);
+    if parsed.result != "Ok" {
+        return;
+    }
+
+    let kind = vector.get("kind").unwrap_or("signed");
+    let (expected_kind, expected_message, expected_security) = match kind {
+        "signed" | "baseline" => ("signed", field("message"), Some(field("security"))),
+        "unsigned" => ("unsigned", field("message"), None),
+        "packet" => ("packet", field("messages"), Some(field("security"))),
+        kind => panic!("{}: unknown kind `{}`", name, kind),
+    };
+    assert_eq!(parsed.kind.as_ref().map(|k| &k[..]), Some(expected_kind), "kind of {}", name);
+    assert_eq!(
+        parsed.message.as_ref().map(|m| &m[..]),
+        Some(expected_message),
+        "message of {}",
+        name
+    );
+    assert_eq!(
+        parsed.next_root.as_ref().map(|r| &r[..]),
+        Some(field("next_root")),
+        "next root of {}",
+        name
+    );
+    assert_eq!(
+        parsed.security.as_ref().map(|s| &s[..]),
+        expected_security,
+        "security of {}",
+        name
+    );
+}
+
+#[test]
+fn vectors_v1() {
+    let file = format::read(include_str!("../vectors/v1.txt")).unwrap();
+    assert_eq!(file.version, format::VERSION);
+    for kind in &["signed", "unsigned", "packet", "baseline"] {
+        assert!(
+            file.vectors.iter().any(|v| {
+                v.get("kind").unwrap_or("signed") == *kind && v.get("result") == Some("Ok")
+            }),
+            "no valid {} vector",
+            kind
+        );
+    }
+    for vector in &file.vectors {
+        check(vector);
+    }
+}
@@ -0,0 +1,374 @@
+//! Reading, writing and running the test vectors in this directory
+//!
+//! Shared by the `vectors` test and the `generate_vectors` example, which
+//! include it with `#[path]`.
+#![allow(dead_code)]
+
+use curl::*;
+use curl_cpu::*;
+use trytes::*;
+use merkle;
+use pascal;
+use sign::iss;
+use mam::{self, MessageType, Security};
+use mam::mask::mask_slice;
+use mam::nonce::NONCE_LENGTH;
+use mam::packet;
+
+/// Version of the vector format and of the payloads it describes
+pub const VERSION: &'static str = "1";
+
+/// Comment written at the top of generated vector files
+pub const HEADER: &'static str = "\
+# Test vectors for masked authenticated messages
+#
+# Each vector is a block of `key = value` lines, separated by blank lines.
+# Trits are written as trytes.
+#
+# Every vector gives a `payload` to parse with `side_key` and `root`, along
+# with the expected `result`, which is `Ok` or the name of the `MamError`.
+# Parsed payloads must yield `message`, or the comma separated `messages` of
+# a packet, the `next_root`, and the `security` level of their signature.
+#
+# The `kind` of a vector is `signed`, `unsigned`, `packet`, or `baseline` for
+# payloads in the format written before version markers and tags were added,
+# which parse as signed messages. Unsigned payloads carry no security level.
+#
+# Vectors with a `seed` were created from
+#
+#   seed, start, count, index, security, side_key, next_root, message(s)
+#
+# where the channel root is the root of the tree of `count` leaves from
+# `start`. Their `root`, `payload` and `result` are filled in by
+#
+#   cargo run -p iota-mam --example generate_vectors -- mam/vectors/v1.txt
+#
+# Other implementations should parse the stored payloads, and may recreate
+# them from the inputs to check their encoders.
+";
+
+/// A single vector, i.e. its fields in the order they were read
+pub struct Vector {
+    pub fields: Vec<(String, String)>,
+}
+
+impl Vector {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| &v[..])
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        match self.fields.iter().position(|&(ref k, _)| k == key) {
+            Some(i) => self.fields[i].1 = value,
+            None => self.fields.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.get("name").unwrap_or("<unnamed>")
+    }
+
+    fn field(&self, key: &str) -> Result<&str, String> {
+        self.get(key).ok_or_else(|| format!("{}: missing `{}`", self.name(), key))
+    }
+
+    fn number<T: ::std::str::FromStr>(&self, key: &str) -> Result<T, String> {
+        self.field(key)?.parse().map_err(|_| {
+            format!("{}: `{}` is not a number", self.name(), key)
+        })
+    }
+}
+
+pub struct VectorFile {
+    pub version: String,
+    pub vectors: Vec<Vector>,
+}
+
+pub fn read(text: &str) -> Result<VectorFile, String> {
+    let mut blocks: Vec<Vector> = Vec::new();
+    let mut current: Vec<(String, String)> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if line.is_empty() {
+            if !current.is_empty() {
+                blocks.push(Vector { fields: current });
+                current = Vec::new();
+            }
+            continue;
+        }
+        let mut parts = line.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => {
+                current.push((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => return Err(format!("line {}: expected `key = value`", number + 1)),
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(Vector { fields: current });
+    }
+    if blocks.is_empty() {
+        return Err("missing version".to_string());
+    }
+    let header = blocks.remove(0);
+    let version = header.field("version")?.to_string();
+    Ok(VectorFile {
+        version: version,
+        vectors: blocks,
+    })
+}
+
+pub fn write(file: &VectorFile) -> String {
+    let mut out = String::from(HEADER);
+    out.push_str(&format!("\nversion = {}\n", file.version));
+    for vector in &file.vectors {
+        out.push('\n');
+        for &(ref key, ref value) in &vector.fields {
+            out.push_str(&format!("{} = {}\n", key, value));
+        }
+    }
+    out
+}
+
+pub fn trits(trytes: &str) -> Vec<Trit> {
+    trytes.chars().flat_map(char_to_trits).cloned().collect()
+}
+
+pub fn trytes(trits: &[Trit]) -> String {
+    trits_to_string(trits).unwrap()
+}
+
+/// The messages of a packet vector
+pub fn messages(vector: &Vector) -> Result<Vec<Vec<Trit>>, String> {
+    Ok(vector.field("messages")?.split(',').map(|m| trits(m.trim())).collect())
+}
+
+/// Root and payload created from the inputs of a vector with a seed
+pub struct Created {
+    pub root: String,
+    pub payload: String,
+}
+
+pub fn create(vector: &Vector) -> Result<Created, String> {
+    let seed = trits(vector.field("seed")?);
+    let start: isize = vector.number("start")?;
+    let count: usize = vector.number("count")?;
+    let index: usize = vector.number("index")?;
+    let security = Security::from_level(vector.number("security")?).map_err(|e| {
+        format!("{}: {:?}", vector.name(), e)
+    })?;
+    let side_key = trits(vector.field("side_key")?);
+    let next_root = trits(vector.field("next_root")?);
+
+    let mut c1 = CpuCurl::<Trit>::default();
+    let mut c2 = CpuCurl::<Trit>::default();
+    let mut c3 = CpuCurl::<Trit>::default();
+    let mut bc = CpuCurl::<BCTrit>::default();
+
+    let tree = merkle::create(&seed, start, count, security.level(), &mut c1, &mut c2, &mut c3);
+    let mut root: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+    merkle::slice(&tree, &mut root);
+    let branch = merkle::branch(&tree, index);
+    let siblings_length = merkle::len(&branch) * HASH_LENGTH;
+    let mut siblings: Vec<Trit> = vec![0; siblings_length];
+    if siblings_length > 0 {
+        merkle::write_branch(&branch, siblings_length - HASH_LENGTH, &mut siblings);
+    }
+
+    let payload = match vector.get("kind").unwrap_or("signed") {
+        "signed" => {
+            let message = trits(vector.field("message")?);
+            let mut payload: Vec<Trit> =
+                vec![0; mam::min_length(message.len(), siblings.len(), index, security)];
+            let length = mam::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+                &seed,
+                &message,
+                &side_key,
+                &root,
+                &siblings,
+                &next_root,
+                start,
+                index,
+                security,
+                &mut payload,
+                &mut c1,
+                &mut c2,
+                &mut bc,
+            ).map_err(|e| format!("{}: {:?}", vector.name(), e))?;
+            payload.truncate(length);
+            payload
+        }
+        "unsigned" => {
+            let message = trits(vector.field("message")?);
+            let mut payload: Vec<Trit> = vec![0; mam::unsigned_min_length(message.len(), index)];
+            mam::create_unsigned(
+                &message,
+                &side_key,
+                &root,
+                &next_root,
+                index,
+                &mut payload,
+                &mut c1,
+            );
+            payload
+        }
+        "packet" => {
+            let messages = messages(vector)?;
+            let messages: Vec<&[Trit]> = messages.iter().map(|m| &m[..]).collect();
+            let mut payload: Vec<Trit> =
+                vec![0; packet::min_length(&messages, siblings.len(), index, security)];
+            let length = packet::create::<CpuCurl<Trit>, CpuCurl<BCTrit>, CpuHam>(
+                &seed,
+                &messages,
+                &side_key,
+                &root,
+                &siblings,
+                &next_root,
+                start,
+                index,
+                security,
+                &mut payload,
+                &mut c1,
+                &mut c2,
+                &mut bc,
+            ).map_err(|e| format!("{}: {:?}", vector.name(), e))?;
+            payload.truncate(length);
+            payload
+        }
+        "baseline" => {
+            let message = trits(vector.field("message")?);
+            create_baseline(
+                &seed,
+                &message,
+                &side_key,
+                &root,
+                &siblings,
+                &next_root,
+                start,
+                index,
+                security,
+            ).map_err(|e| format!("{}: {}", vector.name(), e))?
+        }
+        kind => return Err(format!("{}: unknown kind `{}`", vector.name(), kind)),
+    };
+    Ok(Created {
+        root: trytes(&root),
+        payload: trytes(&payload),
+    })
+}
+
+/// Creates a payload in the format written before version markers and tags
+/// were added, which `mam::create` no longer writes:
+///
+/// ```text
+/// [
+///     Encoded Index,
+///     Encoded Message Length,
+///     encrypted[Next Root, Message, Nonce, Signature, Encoded Number of Siblings, Siblings]
+/// ]
+/// ```
+fn create_baseline(
+    seed: &[Trit],
+    message: &[Trit],
+    side_key: &[Trit],
+    root: &[Trit],
+    siblings: &[Trit],
+    next_root: &[Trit],
+    start: isize,
+    index: usize,
+    security: Security,
+) -> Result<Vec<Trit>, String> {
+    let index_length = pascal::encoded_length(index as isize);
+    let header_length = index_length + pascal::encoded_length(message.len() as isize);
+    let siblings_count = (siblings.len() / HASH_LENGTH) as isize;
+    let message_end = header_length + HASH_LENGTH + message.len();
+    let signature_start = message_end + NONCE_LENGTH;
+    let count_start = signature_start + security.signature_length();
+    let siblings_start = count_start + pascal::encoded_length(siblings_count);
+    let mut payload: Vec<Trit> = vec![0; siblings_start + siblings.len()];
+
+    pascal::encode(index as isize, &mut payload[..index_length]);
+    pascal::encode(
+        message.len() as isize,
+        &mut payload[index_length..header_length],
+    );
+    payload[header_length..header_length + HASH_LENGTH].clone_from_slice(next_root);
+    payload[header_length + HASH_LENGTH..message_end].clone_from_slice(message);
+
+    let mut curl = CpuCurl::<Trit>::default();
+    let mut encr_curl = CpuCurl::<Trit>::default();
+    let mut bcurl = CpuCurl::<BCTrit>::default();
+    encr_curl.absorb(side_key);
+    encr_curl.absorb(root);
+    encr_curl.absorb(&payload[..header_length]);
+    mask_slice(&mut payload[header_length..message_end], &mut encr_curl);
+
+    curl.state_mut().clone_from_slice(encr_curl.state());
+    CpuHam::search(security.level() as u8, 0, NONCE_LENGTH, &mut curl, &mut bcurl)
+        .ok_or_else(|| "no hamming nonce found".to_string())?;
+    payload[message_end..signature_start].clone_from_slice(&curl.rate()[..NONCE_LENGTH]);
+    curl.reset();
+    mask_slice(&mut payload[message_end..signature_start], &mut encr_curl);
+
+    let mut hash: [Trit; HASH_LENGTH] = [0; HASH_LENGTH];
+    hash.clone_from_slice(encr_curl.rate());
+    {
+        let signature = &mut payload[signature_start..count_start];
+        iss::subseed(seed, start + index as isize, &mut signature[..HASH_LENGTH], &mut curl);
+        curl.reset();
+        iss::key(signature, security.level(), &mut curl);
+        curl.reset();
+        iss::signature(&hash, signature, &mut curl);
+        curl.reset();
+    }
+    pascal::encode(siblings_count, &mut payload[count_start..siblings_start]);
+    payload[siblings_start..].clone_from_slice(siblings);
+    mask_slice(&mut payload[signature_start..], &mut encr_curl);
+    Ok(payload)
+}
+
+/// What parsing a payload yields, written like the fields of a vector
+pub struct Parsed {
+    /// `Ok` or the name of the `MamError`
+    pub result: String,
+    pub kind: Option<String>,
+    /// The message, or the comma separated messages of a packet
+    pub message: Option<String>,
+    pub next_root: Option<String>,
+    pub security: Option<String>,
+}
+
+/// Parses a `payload` with `side_key` and `root`
+pub fn parse(side_key: &str, root: &str, payload: &str) -> Parsed {
+    let mut payload = trits(payload);
+    let mut curl = CpuCurl::<Trit>::default();
+    match mam::parse(&mut payload, &trits(side_key), &trits(root), &mut curl) {
+        Ok(parsed) => {
+            let message = if parsed.kind == MessageType::Packet {
+                let messages: Result<Vec<String>, _> = packet::messages(parsed.message(&payload))
+                    .map(|m| m.map(trytes))
+                    .collect();
+                messages.map(|m| m.join(",")).unwrap_or_default()
+            } else {
+                trytes(parsed.message(&payload))
+            };
+            Parsed {
+                result: "Ok".to_string(),
+                kind: Some(format!("{:?}", parsed.kind).to_lowercase()),
+                message: Some(message),
+                next_root: Some(trytes(parsed.next(&payload))),
+                security: parsed.security.map(|s| s.level().to_string()),
+            }
+        }
+        Err(e) => Parsed {
+            result: format!("{:?}", e),
+            kind: None,
+            message: None,
+            next_root: None,
+            security: None,
+        },
+    }
+}
@@ -0,0 +1,219 @@
+//! `#[derive(TritEncode, TritDecode)]` for the traits of `iota-pascal`
+//!
+//! Fields are encoded in declaration order. Enums are prefixed with the
+//! index of their variant. Type parameters are required to implement the
+//! derived trait.
+extern crate proc_macro;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use syn::{Body, DeriveInput, Generics, Ident, PolyTraitRef, TraitBoundModifier, Ty,
+          TyParamBound, VariantData, WhereBoundPredicate, WherePredicate};
+
+#[proc_macro_derive(TritEncode)]
+pub fn derive_trit_encode(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_derive_input(&input.to_string()).unwrap();
+    impl_encode(&ast).parse().unwrap()
+}
+
+#[proc_macro_derive(TritDecode)]
+pub fn derive_trit_decode(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_derive_input(&input.to_string()).unwrap();
+    impl_decode(&ast).parse().unwrap()
+}
+
+/// The generics of `ast`, with every type parameter bound by the trait at
+/// `bound` in the where clause
+fn with_bound(ast: &DeriveInput, bound: &str) -> Generics {
+    let bound = syn::parse_path(bound).unwrap();
+    let mut generics = ast.generics.clone();
+    let predicates: Vec<WherePredicate> = ast.generics
+        .ty_params
+        .iter()
+        .map(|param| {
+            WherePredicate::BoundPredicate(WhereBoundPredicate {
+                bound_lifetimes: Vec::new(),
+                bounded_ty: Ty::Path(None, param.ident.clone().into()),
+                bounds: vec![
+                    TyParamBound::Trait(
+                        PolyTraitRef {
+                            bound_lifetimes: Vec::new(),
+                            trait_ref: bound.clone(),
+                        },
+                        TraitBoundModifier::None
+                    ),
+                ],
+            })
+        })
+        .collect();
+    generics.where_clause.predicates.extend(predicates);
+    generics
+}
+
+/// Names bound to the fields of `data`, in order. They are prefixed so that
+/// fields cannot shadow the locals of the generated code.
+fn bindings(data: &VariantData) -> Vec<Ident> {
+    data.fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match field.ident {
+            Some(ref ident) => Ident::new(format!("__pascal_field_{}", ident)),
+            None => Ident::new(format!("__pascal_field{}", i)),
+        })
+        .collect()
+}
+
+/// A pattern binding the fields of `data` by reference, or constructing it
+/// from the bindings if `by_ref` is false
+fn pattern(path: &quote::Tokens, data: &VariantData, by_ref: bool) -> quote::Tokens {
+    let names = bindings(data);
+    let refs = if by_ref { quote!(ref) } else { quote!() };
+    let binds: Vec<quote::Tokens> = names.iter().map(|n| quote!(#refs #n)).collect();
+    match *data {
+        VariantData::Struct(ref fields) => {
+            let idents: Vec<&Ident> = fields.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+            quote!(#path { #(#idents: #binds),* })
+        }
+        VariantData::Tuple(_) => quote!(#path ( #(#binds),* )),
+        VariantData::Unit => quote!(#path),
+    }
+}
+
+/// Arms matching each variant, or the struct itself, with the fields bound
+/// and the index of the variant
+fn arms(ast: &DeriveInput) -> Vec<(quote::Tokens, Option<u64>, Vec<Ident>)> {
+    let name = &ast.ident;
+    match ast.body {
+        Body::Struct(ref data) => vec![(pattern(&quote!(#name), data, true), None, bindings(data))],
+        Body::Enum(ref variants) => {
+            variants
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let ident = &v.ident;
+                    (
+                        pattern(&quote!(#name::#ident), &v.data, true),
+                        Some(i as u64),
+                        bindings(&v.data),
+                    )
+                })
+                .collect()
+        }
+    }
+}
+
+fn impl_encode(ast: &DeriveInput) -> quote::Tokens {
+    let name = &ast.ident;
+    let generics = with_bound(ast, "::iota_pascal::TritEncode");
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let mut length_arms = Vec::new();
+    let mut encode_arms = Vec::new();
+    for (pat, index, names) in arms(ast) {
+        let index = match index {
+            Some(i) => quote!(Some(#i)),
+            None => quote!(None::<u64>),
+        };
+        let names = &names;
+        length_arms.push(quote! {
+            #pat => {
+                #index.map_or(0, |i| ::iota_pascal::TritEncode::encoded_length(&i))
+                    #( + ::iota_pascal::TritEncode::encoded_length(#names) )*
+            }
+        });
+        encode_arms.push(quote! {
+            #pat => {
+                let mut __pascal_pos = #index.map_or(0, |i| {
+                    ::iota_pascal::TritEncode::encode(&i, __pascal_out)
+                });
+                #(
+                    __pascal_pos += ::iota_pascal::TritEncode::encode(
+                        #names,
+                        &mut __pascal_out[__pascal_pos..],
+                    );
+                )*
+                __pascal_pos
+            }
+        });
+    }
+    quote! {
+        impl #impl_generics ::iota_pascal::TritEncode for #name #ty_generics #where_clause {
+            fn encoded_length(&self) -> usize {
+                match *self {
+                    #(#length_arms)*
+                }
+            }
+
+            #[allow(unused_mut)]
+            fn encode(&self, __pascal_out: &mut [::iota_pascal::Trit]) -> usize {
+                match *self {
+                    #(#encode_arms)*
+                }
+            }
+        }
+    }
+}
+
+/// Statements decoding the fields of `data` into their bindings, followed by
+/// the expression constructing it at `path`
+fn decode_fields(path: &quote::Tokens, data: &VariantData) -> quote::Tokens {
+    let names = bindings(data);
+    let construct = pattern(path, data, false);
+    quote! {
+        #(
+            let #names = {
+                let (__pascal_value, __pascal_length) =
+                    ::iota_pascal::TritDecode::decode(&__pascal_input[__pascal_pos..])?;
+                __pascal_pos += __pascal_length;
+                __pascal_value
+            };
+        )*
+        Ok((#construct, __pascal_pos))
+    }
+}
+
+fn impl_decode(ast: &DeriveInput) -> quote::Tokens {
+    let name = &ast.ident;
+    let generics = with_bound(ast, "::iota_pascal::TritDecode");
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let body = match ast.body {
+        Body::Struct(ref data) => {
+            let fields = decode_fields(&quote!(#name), data);
+            quote! {
+                let mut __pascal_pos = 0;
+                #fields
+            }
+        }
+        Body::Enum(ref variants) => {
+            let variant_arms: Vec<quote::Tokens> = variants
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let i = i as u64;
+                    let ident = &v.ident;
+                    let fields = decode_fields(&quote!(#name::#ident), &v.data);
+                    quote!(#i => { #fields })
+                })
+                .collect();
+            quote! {
+                let (__pascal_index, mut __pascal_pos): (u64, usize) =
+                    ::iota_pascal::TritDecode::decode(__pascal_input)?;
+                match __pascal_index {
+                    #(#variant_arms)*
+                    _ => Err(::iota_pascal::PascalError::InvalidEncoding),
+                }
+            }
+        }
+    };
+    quote! {
+        impl #impl_generics ::iota_pascal::TritDecode for #name #ty_generics #where_clause {
+            #[allow(unused_mut)]
+            fn decode(
+                __pascal_input: &[::iota_pascal::Trit],
+            ) -> Result<(Self, usize), ::iota_pascal::PascalError> {
+                #body
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+extern crate iota_pascal;
+#[macro_use]
+extern crate iota_pascal_derive;
+
+use iota_pascal::*;
+
+#[derive(TritEncode, TritDecode, PartialEq, Debug)]
+struct Reading {
+    sensor: u32,
+    celsius: i16,
+    label: String,
+    samples: Vec<i64>,
+    raw: Trits,
+}
+
+#[derive(TritEncode, TritDecode, PartialEq, Debug)]
+struct Pair(u8, bool);
+
+#[derive(TritEncode, TritDecode, PartialEq, Debug)]
+struct Tagged<T> {
+    tag: u8,
+    value: T,
+}
+
+/// Fields named like the locals of the generated code
+#[derive(TritEncode, TritDecode, PartialEq, Debug)]
+struct Cursor {
+    pos: u16,
+    out: Vec<u8>,
+    input: Trits,
+    length: i32,
+    value: bool,
+}
+
+#[derive(TritEncode, TritDecode, PartialEq, Debug)]
+enum Event {
+    Started,
+    Reading(Reading),
+    Moved { from: Pair, to: Option<Pair> },
+}
+
+#[test]
+fn structs_round_trip() {
+    let reading = Reading {
+        sensor: 7,
+        celsius: -21,
+        label: String::from("greenhouse"),
+        samples: vec![1, -2, 3],
+        raw: Trits(vec![1, 0, -1]),
+    };
+    let trits = to_trits(&reading);
+    assert_eq!(trits.len(), reading.encoded_length());
+    assert_eq!(from_trits::<Reading>(&trits), Ok(reading));
+    assert_eq!(from_trits::<Pair>(&to_trits(&Pair(3, true))), Ok(Pair(3, true)));
+}
+
+#[test]
+fn generic_structs_round_trip() {
+    let tagged = Tagged {
+        tag: 2,
+        value: Pair(5, false),
+    };
+    assert_eq!(from_trits::<Tagged<Pair>>(&to_trits(&tagged)), Ok(tagged));
+}
+
+#[test]
+fn fields_named_like_locals_round_trip() {
+    let cursor = Cursor {
+        pos: 300,
+        out: vec![1, 2],
+        input: Trits(vec![-1, 1]),
+        length: -4,
+        value: true,
+    };
+    let trits = to_trits(&cursor);
+    assert_eq!(trits.len(), cursor.encoded_length());
+    assert_eq!(from_trits::<Cursor>(&trits), Ok(cursor));
+}
+
+#[test]
+fn enums_round_trip() {
+    let events = vec![
+        Event::Started,
+        Event::Reading(Reading {
+            sensor: 1,
+            celsius: 0,
+            label: String::new(),
+            samples: Vec::new(),
+            raw: Trits(Vec::new()),
+        }),
+        Event::Moved {
+            from: Pair(1, false),
+            to: None,
+        },
+        Event::Moved {
+            from: Pair(1, false),
+            to: Some(Pair(2, true)),
+        },
+    ];
+    let trits = to_trits(&events);
+    assert_eq!(from_trits::<Vec<Event>>(&trits), Ok(events));
+    assert_eq!(
+        from_trits::<Event>(&to_trits(&3u64)),
+        Err(PascalError::InvalidEncoding)
+    );
+}
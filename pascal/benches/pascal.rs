@@ -0,0 +1,51 @@
+#[macro_use]
+extern crate criterion;
+extern crate iota_pascal as pascal;
+
+use criterion::Criterion;
+use pascal::*;
+
+const NUMBERS: [i64; 4] = [0, 1_000, 1_000_000, 1_000_000_000_000];
+
+fn encoding(c: &mut Criterion) {
+    c.bench_function_over_inputs(
+        "pascal::encode",
+        |b, &&n| {
+            let mut out: Vec<Trit> = vec![0; encoded_length(n as isize)];
+            b.iter(|| encode(n as isize, &mut out));
+        },
+        &NUMBERS,
+    );
+    c.bench_function_over_inputs(
+        "pascal::encode_unsigned",
+        |b, &&n| {
+            let mut out: Vec<Trit> = vec![0; encoded_length_unsigned(n as u64)];
+            b.iter(|| encode_unsigned(n as u64, &mut out));
+        },
+        &NUMBERS,
+    );
+}
+
+fn decoding(c: &mut Criterion) {
+    c.bench_function_over_inputs(
+        "pascal::decode",
+        |b, &&n| {
+            let mut encoded: Vec<Trit> = vec![0; encoded_length(n as isize)];
+            encode(n as isize, &mut encoded);
+            b.iter(|| decode(&encoded));
+        },
+        &NUMBERS,
+    );
+    c.bench_function_over_inputs(
+        "pascal::decode_unsigned",
+        |b, &&n| {
+            let mut encoded: Vec<Trit> = vec![0; encoded_length_unsigned(n as u64)];
+            encode_unsigned(n as u64, &mut encoded);
+            b.iter(|| decode_unsigned(&encoded));
+        },
+        &NUMBERS,
+    );
+}
+
+criterion_group!(benches, encoding, decoding);
+criterion_main!(benches);
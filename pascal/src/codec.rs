@@ -0,0 +1,360 @@
+//! Serialization of application types to trits
+//!
+//! Integers use the pascal encoding, and collections are prefixed with their
+//! length like in `sequence`. Structs and enums can implement both traits
+//! with `#[derive(TritEncode, TritDecode)]` from `iota-pascal-derive`, which
+//! encodes fields in order, after the index of the variant for enums.
+use alloc::Vec;
+use alloc::string::String;
+use trytes::constants::*;
+use errors::*;
+use wide::*;
+
+/// Trits used for a single byte of a string
+const TRITS_PER_BYTE: usize = 2 * TRITS_PER_TRYTE;
+
+pub trait TritEncode {
+    /// Length of the encoding of `self`
+    fn encoded_length(&self) -> usize;
+
+    /// Encodes `self` to the start of `out`, returning the length of the
+    /// encoding
+    fn encode(&self, out: &mut [Trit]) -> usize;
+}
+
+pub trait TritDecode: Sized {
+    /// Decodes a value at the start of `input`, returning it along with the
+    /// length of its encoding
+    fn decode(input: &[Trit]) -> Result<(Self, usize), PascalError>;
+}
+
+/// Encodes `value` to a new vector of trits
+pub fn to_trits<T: TritEncode + ?Sized>(value: &T) -> Vec<Trit> {
+    let mut out: Vec<Trit> = vec![0; value.encoded_length()];
+    value.encode(&mut out);
+    out
+}
+
+/// Decodes a value which must take up all of `input`
+pub fn from_trits<T: TritDecode>(input: &[Trit]) -> Result<T, PascalError> {
+    let (value, length) = T::decode(input)?;
+    if length != input.len() {
+        return Err(PascalError::InvalidEncoding);
+    }
+    Ok(value)
+}
+
+/// Raw trits, encoded as a length-prefixed trit string
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Trits(pub Vec<Trit>);
+
+macro_rules! signed {
+    ($($t:ty),*) => {$(
+        impl TritEncode for $t {
+            fn encoded_length(&self) -> usize {
+                encoded_length_i64(*self as i64)
+            }
+
+            fn encode(&self, out: &mut [Trit]) -> usize {
+                encode_i64(*self as i64, out);
+                encoded_length_i64(*self as i64)
+            }
+        }
+
+        impl TritDecode for $t {
+            fn decode(input: &[Trit]) -> Result<(Self, usize), PascalError> {
+                let (v, length) = decode_i64(input)?;
+                if v < <$t>::min_value() as i64 || v > <$t>::max_value() as i64 {
+                    return Err(PascalError::Overflow);
+                }
+                Ok((v as $t, length))
+            }
+        }
+    )*}
+}
+
+macro_rules! unsigned {
+    ($($t:ty),*) => {$(
+        impl TritEncode for $t {
+            fn encoded_length(&self) -> usize {
+                encoded_length_u64(*self as u64)
+            }
+
+            fn encode(&self, out: &mut [Trit]) -> usize {
+                encode_u64(*self as u64, out);
+                encoded_length_u64(*self as u64)
+            }
+        }
+
+        impl TritDecode for $t {
+            fn decode(input: &[Trit]) -> Result<(Self, usize), PascalError> {
+                let (v, length) = decode_u64(input)?;
+                if v > <$t>::max_value() as u64 {
+                    return Err(PascalError::Overflow);
+                }
+                Ok((v as $t, length))
+            }
+        }
+    )*}
+}
+
+signed!(i8, i16, i32, i64, isize);
+unsigned!(u8, u16, u32, u64, usize);
+
+impl TritEncode for bool {
+    fn encoded_length(&self) -> usize {
+        (*self as u8).encoded_length()
+    }
+
+    fn encode(&self, out: &mut [Trit]) -> usize {
+        (*self as u8).encode(out)
+    }
+}
+
+impl TritDecode for bool {
+    fn decode(input: &[Trit]) -> Result<(Self, usize), PascalError> {
+        match u8::decode(input)? {
+            (0, length) => Ok((false, length)),
+            (1, length) => Ok((true, length)),
+            _ => Err(PascalError::InvalidEncoding),
+        }
+    }
+}
+
+/// Decodes a length prefix, checking that at least `min_item_length` trits
+/// per item are left in `input`
+fn decode_length(input: &[Trit], min_item_length: usize) -> Result<(usize, usize), PascalError> {
+    let (length, start) = decode_u64(input)?;
+    if length.saturating_mul(min_item_length as u64) > (input.len() - start) as u64 {
+        return Err(PascalError::Truncated);
+    }
+    Ok((length as usize, start))
+}
+
+impl TritEncode for Trits {
+    fn encoded_length(&self) -> usize {
+        self.0.len().encoded_length() + self.0.len()
+    }
+
+    fn encode(&self, out: &mut [Trit]) -> usize {
+        let start = self.0.len().encode(out);
+        out[start..start + self.0.len()].clone_from_slice(&self.0);
+        start + self.0.len()
+    }
+}
+
+impl TritDecode for Trits {
+    fn decode(input: &[Trit]) -> Result<(Self, usize), PascalError> {
+        let (length, start) = decode_length(input, 1)?;
+        Ok((
+            Trits(input[start..start + length].to_vec()),
+            start + length,
+        ))
+    }
+}
+
+impl TritEncode for str {
+    fn encoded_length(&self) -> usize {
+        self.len().encoded_length() + self.len() * TRITS_PER_BYTE
+    }
+
+    fn encode(&self, out: &mut [Trit]) -> usize {
+        let mut pos = self.len().encode(out);
+        for byte in self.bytes() {
+            encode_byte(byte, &mut out[pos..pos + TRITS_PER_BYTE]);
+            pos += TRITS_PER_BYTE;
+        }
+        pos
+    }
+}
+
+impl TritEncode for String {
+    fn encoded_length(&self) -> usize {
+        self.as_str().encoded_length()
+    }
+
+    fn encode(&self, out: &mut [Trit]) -> usize {
+        self.as_str().encode(out)
+    }
+}
+
+impl TritDecode for String {
+    fn decode(input: &[Trit]) -> Result<(Self, usize), PascalError> {
+        let (length, mut pos) = decode_length(input, TRITS_PER_BYTE)?;
+        let mut bytes: Vec<u8> = Vec::with_capacity(length);
+        for _ in 0..length {
+            bytes.push(decode_byte(&input[pos..pos + TRITS_PER_BYTE])?);
+            pos += TRITS_PER_BYTE;
+        }
+        String::from_utf8(bytes)
+            .map(|s| (s, pos))
+            .map_err(|_| PascalError::InvalidEncoding)
+    }
+}
+
+fn encode_byte(byte: u8, out: &mut [Trit]) {
+    let mut value = byte as isize;
+    for t in out.iter_mut() {
+        let r = ((value + 1) % RADIX as isize) - 1;
+        *t = r as Trit;
+        value = (value - r) / RADIX as isize;
+    }
+}
+
+fn decode_byte(trits: &[Trit]) -> Result<u8, PascalError> {
+    let value = trits.iter().rev().fold(0, |acc, &t| acc * RADIX as isize + t as isize);
+    if value < 0 || value > u8::max_value() as isize {
+        return Err(PascalError::Overflow);
+    }
+    Ok(value as u8)
+}
+
+impl<T: TritEncode> TritEncode for [T] {
+    fn encoded_length(&self) -> usize {
+        self.iter().fold(
+            self.len().encoded_length(),
+            |acc, item| acc + item.encoded_length(),
+        )
+    }
+
+    fn encode(&self, out: &mut [Trit]) -> usize {
+        let mut pos = self.len().encode(out);
+        for item in self {
+            pos += item.encode(&mut out[pos..]);
+        }
+        pos
+    }
+}
+
+impl<T: TritEncode> TritEncode for Vec<T> {
+    fn encoded_length(&self) -> usize {
+        self[..].encoded_length()
+    }
+
+    fn encode(&self, out: &mut [Trit]) -> usize {
+        self[..].encode(out)
+    }
+}
+
+impl<T: TritDecode> TritDecode for Vec<T> {
+    fn decode(input: &[Trit]) -> Result<(Self, usize), PascalError> {
+        // Items may encode to no trits at all, e.g. unit structs, so the
+        // length is only trusted as far as the remaining input goes
+        let (length, mut pos) = decode_length(input, 0)?;
+        let mut items: Vec<T> = Vec::with_capacity(length.min(input.len() - pos));
+        for _ in 0..length {
+            let (item, item_length) = T::decode(&input[pos..])?;
+            items.push(item);
+            pos += item_length;
+        }
+        Ok((items, pos))
+    }
+}
+
+impl<T: TritEncode> TritEncode for Option<T> {
+    fn encoded_length(&self) -> usize {
+        match *self {
+            Some(ref v) => true.encoded_length() + v.encoded_length(),
+            None => false.encoded_length(),
+        }
+    }
+
+    fn encode(&self, out: &mut [Trit]) -> usize {
+        match *self {
+            Some(ref v) => {
+                let start = true.encode(out);
+                start + v.encode(&mut out[start..])
+            }
+            None => false.encode(out),
+        }
+    }
+}
+
+impl<T: TritDecode> TritDecode for Option<T> {
+    fn decode(input: &[Trit]) -> Result<(Self, usize), PascalError> {
+        match bool::decode(input)? {
+            (true, start) => {
+                let (v, length) = T::decode(&input[start..])?;
+                Ok((Some(v), start + length))
+            }
+            (false, start) => Ok((None, start)),
+        }
+    }
+}
+
+/// Arrays have a fixed length, so unlike slices they are not length-prefixed
+macro_rules! array {
+    ($($n:expr),*) => {$(
+        impl<T: TritEncode> TritEncode for [T; $n] {
+            fn encoded_length(&self) -> usize {
+                self.iter().fold(0, |acc, item| acc + item.encoded_length())
+            }
+
+            fn encode(&self, out: &mut [Trit]) -> usize {
+                let mut pos = 0;
+                for item in self.iter() {
+                    pos += item.encode(&mut out[pos..]);
+                }
+                pos
+            }
+        }
+
+        impl<T: TritDecode + Default + Copy> TritDecode for [T; $n] {
+            fn decode(input: &[Trit]) -> Result<(Self, usize), PascalError> {
+                let mut items: [T; $n] = [T::default(); $n];
+                let mut pos = 0;
+                for item in items.iter_mut() {
+                    let (v, length) = T::decode(&input[pos..])?;
+                    *item = v;
+                    pos += length;
+                }
+                Ok((items, pos))
+            }
+        }
+    )*}
+}
+
+array!(
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+    25, 26, 27, 28, 29, 30, 31, 32
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::Vec;
+    use alloc::string::String;
+
+    #[test]
+    fn values_round_trip() {
+        assert_eq!(from_trits::<i32>(&to_trits(&-123456i32)), Ok(-123456));
+        assert_eq!(from_trits::<u8>(&to_trits(&300u16)), Err(PascalError::Overflow));
+        assert_eq!(from_trits::<bool>(&to_trits(&true)), Ok(true));
+
+        let s = String::from("Grüße, IOTA");
+        assert_eq!(from_trits::<String>(&to_trits(&s)), Ok(s));
+        let trits = Trits(vec![1, 0, -1, 1]);
+        assert_eq!(from_trits::<Trits>(&to_trits(&trits)), Ok(trits));
+
+        let list: Vec<Option<i64>> = vec![Some(-1), None, Some(i64::max_value())];
+        assert_eq!(from_trits::<Vec<Option<i64>>>(&to_trits(&list)), Ok(list));
+        let array: [u16; 3] = [1, 2, 65535];
+        let encoded = to_trits(&array);
+        assert_eq!(from_trits::<[u16; 3]>(&encoded), Ok(array));
+        assert_eq!(
+            from_trits::<[u16; 3]>(&encoded[..encoded.len() - 1]),
+            Err(PascalError::Truncated)
+        );
+    }
+
+    #[test]
+    fn empty_items_round_trip() {
+        let empty: Vec<[u8; 0]> = vec![[], [], []];
+        let encoded = to_trits(&empty);
+        assert_eq!(encoded.len(), 3usize.encoded_length());
+        assert_eq!(from_trits::<Vec<[u8; 0]>>(&encoded), Ok(empty));
+
+        let nested: Vec<Vec<u8>> = vec![Vec::new(), vec![1], Vec::new()];
+        assert_eq!(from_trits::<Vec<Vec<u8>>>(&to_trits(&nested)), Ok(nested));
+    }
+}
@@ -0,0 +1,256 @@
+//! Cursors reading and writing pascal encoded numbers and trits in sequence
+//!
+//! They keep track of the position in the trits, so that callers do not
+//! have to add up the lengths of what they read or wrote.
+use core::mem;
+use trytes::constants::*;
+use errors::*;
+use wide::*;
+use unsigned::*;
+
+/// Reads from the start of some trits onwards
+pub struct Reader<'a> {
+    trits: &'a [Trit],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(trits: &'a [Trit]) -> Self {
+        Reader {
+            trits: trits,
+            pos: 0,
+        }
+    }
+
+    /// Number of trits read so far
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Number of trits left to read
+    pub fn remaining(&self) -> usize {
+        self.trits.len()
+    }
+
+    /// The trits left to read
+    pub fn rest(&self) -> &'a [Trit] {
+        self.trits
+    }
+
+    /// Reads the next `n` trits
+    pub fn read_trits(&mut self, n: usize) -> Result<&'a [Trit], PascalError> {
+        if n > self.trits.len() {
+            return Err(PascalError::Truncated);
+        }
+        let trits = self.trits;
+        let (head, tail) = trits.split_at(n);
+        self.trits = tail;
+        self.pos += n;
+        Ok(head)
+    }
+
+    /// Reads a number encoded with `encode_i64`
+    pub fn read_pascal(&mut self) -> Result<i64, PascalError> {
+        let (value, length) = decode_i64(self.trits)?;
+        self.read_trits(length)?;
+        Ok(value)
+    }
+
+    /// Reads a number encoded with `encode_unsigned`
+    pub fn read_unsigned(&mut self) -> Result<u64, PascalError> {
+        let (value, length) = decode_unsigned(self.trits)?;
+        self.read_trits(length)?;
+        Ok(value)
+    }
+}
+
+/// Reads from the start of some trits onwards, handing out the trits read
+/// for modification, e.g. to unmask them in place
+pub struct ReaderMut<'a> {
+    trits: &'a mut [Trit],
+    pos: usize,
+}
+
+impl<'a> ReaderMut<'a> {
+    pub fn new(trits: &'a mut [Trit]) -> Self {
+        ReaderMut {
+            trits: trits,
+            pos: 0,
+        }
+    }
+
+    /// Number of trits read so far
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Number of trits left to read
+    pub fn remaining(&self) -> usize {
+        self.trits.len()
+    }
+
+    /// The trits left to read
+    pub fn rest(&self) -> &[Trit] {
+        &*self.trits
+    }
+
+    /// Reads the next `n` trits
+    pub fn read_trits(&mut self, n: usize) -> Result<&'a mut [Trit], PascalError> {
+        if n > self.trits.len() {
+            return Err(PascalError::Truncated);
+        }
+        let trits = mem::replace(&mut self.trits, &mut []);
+        let (head, tail) = trits.split_at_mut(n);
+        self.trits = tail;
+        self.pos += n;
+        Ok(head)
+    }
+
+    /// Reads a number encoded with `encode_i64`
+    pub fn read_pascal(&mut self) -> Result<i64, PascalError> {
+        let (value, length) = decode_i64(self.trits)?;
+        self.read_trits(length)?;
+        Ok(value)
+    }
+
+    /// Reads a number encoded with `encode_unsigned`
+    pub fn read_unsigned(&mut self) -> Result<u64, PascalError> {
+        let (value, length) = decode_unsigned(self.trits)?;
+        self.read_trits(length)?;
+        Ok(value)
+    }
+}
+
+/// Writes from the start of some trits onwards.
+///
+/// Writing beyond the end of the trits panics, like `encode` does.
+pub struct Writer<'a> {
+    out: &'a mut [Trit],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(out: &'a mut [Trit]) -> Self {
+        Writer { out: out, pos: 0 }
+    }
+
+    /// Number of trits written so far
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Number of trits left to write to
+    pub fn remaining(&self) -> usize {
+        self.out.len() - self.pos
+    }
+
+    /// Skips the next `n` trits, returning them to be written to
+    pub fn reserve(&mut self, n: usize) -> &mut [Trit] {
+        let start = self.pos;
+        self.pos += n;
+        &mut self.out[start..start + n]
+    }
+
+    /// The trits written from `start` up to the current position
+    pub fn written(&mut self, start: usize) -> &mut [Trit] {
+        &mut self.out[start..self.pos]
+    }
+
+    pub fn write_trits(&mut self, trits: &[Trit]) {
+        self.reserve(trits.len()).clone_from_slice(trits);
+    }
+
+    /// Writes a number with `encode_i64`
+    pub fn write_pascal(&mut self, value: i64) {
+        let length = encoded_length_i64(value);
+        encode_i64(value, self.reserve(length));
+    }
+
+    /// Writes a number with `encode_unsigned`
+    pub fn write_unsigned(&mut self, value: u64) {
+        let length = encoded_length_unsigned(value);
+        encode_unsigned(value, self.reserve(length));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::Vec;
+
+    #[test]
+    fn reads_what_was_written() {
+        let trits: [Trit; 4] = [1, -1, 0, 1];
+        let mut out: Vec<Trit> = vec![0; 40];
+        let end = {
+            let mut writer = Writer::new(&mut out);
+            writer.write_pascal(-1234567);
+            writer.write_trits(&trits);
+            writer.write_unsigned(42);
+            for t in writer.written(0).iter_mut() {
+                *t = -*t;
+            }
+            writer.position()
+        };
+        for t in out[..end].iter_mut() {
+            *t = -*t;
+        }
+
+        let mut reader = Reader::new(&out[..end]);
+        assert_eq!(reader.read_pascal(), Ok(-1234567));
+        assert_eq!(reader.read_trits(4), Ok(&trits[..]));
+        assert_eq!(reader.read_unsigned(), Ok(42));
+        assert_eq!(reader.remaining(), 0);
+        assert_eq!(reader.position(), end);
+        assert_eq!(reader.read_trits(1), Err(PascalError::Truncated));
+
+        let mut reader = ReaderMut::new(&mut out[..end]);
+        assert_eq!(reader.read_pascal(), Ok(-1234567));
+        let read = reader.read_trits(4).unwrap();
+        assert_eq!(reader.read_unsigned(), Ok(42));
+        read[0] = 0;
+        assert_eq!(read, &[0, -1, 0, 1]);
+    }
+    quickcheck! {
+        fn reads_random_sequences(signed: Vec<i64>, unsigned: Vec<u64>, digits: Vec<u8>) -> bool {
+            let trits: Vec<Trit> = digits.iter().map(|&d| (d % 3) as Trit - 1).collect();
+            let length = signed.iter().map(|&i| encoded_length_i64(i)).sum::<usize>() +
+                unsigned.iter().map(|&i| encoded_length_unsigned(i)).sum::<usize>() +
+                trits.len();
+            let mut out: Vec<Trit> = vec![0; length];
+            {
+                let mut writer = Writer::new(&mut out);
+                for (&i, &u) in signed.iter().zip(unsigned.iter()) {
+                    writer.write_pascal(i);
+                    writer.write_unsigned(u);
+                }
+                for &i in signed.iter().skip(unsigned.len()) {
+                    writer.write_pascal(i);
+                }
+                for &u in unsigned.iter().skip(signed.len()) {
+                    writer.write_unsigned(u);
+                }
+                writer.write_trits(&trits);
+                assert_eq!(writer.remaining(), 0);
+            }
+
+            let mut reader = Reader::new(&out);
+            for (&i, &u) in signed.iter().zip(unsigned.iter()) {
+                if reader.read_pascal() != Ok(i) || reader.read_unsigned() != Ok(u) {
+                    return false;
+                }
+            }
+            for &i in signed.iter().skip(unsigned.len()) {
+                if reader.read_pascal() != Ok(i) {
+                    return false;
+                }
+            }
+            for &u in unsigned.iter().skip(signed.len()) {
+                if reader.read_unsigned() != Ok(u) {
+                    return false;
+                }
+            }
+            reader.read_trits(trits.len()) == Ok(&trits[..]) && reader.remaining() == 0
+        }
+    }
+}
@@ -0,0 +1,9 @@
+#[derive(Eq, PartialEq, Debug)]
+pub enum PascalError {
+    /// Value does not fit into the requested width
+    Overflow,
+    /// Input ended before the end of the encoding
+    Truncated,
+    /// Encoder trits decode to a negative number
+    InvalidEncoding,
+}
@@ -7,5 +7,24 @@ extern crate alloc;
 
 extern crate iota_trytes as trytes;
 
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
+
 mod pascal;
+mod errors;
+mod wide;
+mod unsigned;
+mod sequence;
+mod tlv;
+mod codec;
+mod cursor;
 pub use pascal::*;
+pub use errors::*;
+pub use wide::*;
+pub use unsigned::*;
+pub use sequence::*;
+pub use tlv::*;
+pub use codec::*;
+pub use cursor::*;
+pub use trytes::constants::Trit;
@@ -1,7 +1,7 @@
 use trytes::constants::*;
 use trytes::num;
 
-const ZERO: [Trit; 4] = [1, 0, 0, -1];
+pub(crate) const ZERO: [Trit; 4] = [1, 0, 0, -1];
 
 fn end(trits: &[Trit]) -> usize {
     if num::trits2int(&trits[..TRITS_PER_TRYTE]).is_positive() {
@@ -116,6 +116,7 @@ pub fn encode(input: isize, out: &mut [Trit]) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use quickcheck::TestResult;
     use alloc::Vec;
 
     fn test_encoding(i: isize) {
@@ -140,4 +141,16 @@ mod tests {
             test_encoding(-i);
         }
     }
+    quickcheck! {
+        fn round_trips_numbers(value: isize, shift: u8) -> TestResult {
+            let i = value.wrapping_shl(shift as u32 % 32);
+            // larger magnitudes overflow the length computation on 32-bit
+            if i.checked_abs().map_or(true, |a| a > isize::max_value() / 2) {
+                return TestResult::discard();
+            }
+            let mut e: Vec<Trit> = vec![0; encoded_length(i)];
+            encode(i, &mut e);
+            TestResult::from_bool(decode(&e) == (i, e.len()))
+        }
+    }
 }
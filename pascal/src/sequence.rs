@@ -0,0 +1,99 @@
+//! Pascal encoded trit strings and lists of integers
+//!
+//! Both are prefixed by their pascal encoded length:
+//!
+//! ```text
+//! [Length, Trits...]
+//! [Count, Integer, Integer, ...]
+//! ```
+use alloc::Vec;
+use trytes::constants::*;
+use errors::*;
+use wide::*;
+
+/// Length of the encoding of the trit string `value`
+pub fn string_encoded_length(value: &[Trit]) -> usize {
+    encoded_length_u64(value.len() as u64) + value.len()
+}
+
+/// Encodes the trit string `value` to the start of `out`, returning the
+/// length of the encoding
+pub fn encode_string(value: &[Trit], out: &mut [Trit]) -> usize {
+    let start = encoded_length_u64(value.len() as u64);
+    encode_u64(value.len() as u64, out);
+    out[start..start + value.len()].clone_from_slice(value);
+    start + value.len()
+}
+
+/// Decodes a trit string at the start of `input`, returning it along with
+/// the length of its encoding
+pub fn decode_string(input: &[Trit]) -> Result<(&[Trit], usize), PascalError> {
+    let (length, start) = decode_u64(input)?;
+    if length > (input.len() - start) as u64 {
+        return Err(PascalError::Truncated);
+    }
+    let end = start + length as usize;
+    Ok((&input[start..end], end))
+}
+
+/// Length of the encoding of the list of `values`
+pub fn list_encoded_length(values: &[i64]) -> usize {
+    values.iter().fold(
+        encoded_length_u64(values.len() as u64),
+        |acc, &v| acc + encoded_length_i64(v),
+    )
+}
+
+/// Encodes the list of `values` to the start of `out`, returning the length
+/// of the encoding
+pub fn encode_list(values: &[i64], out: &mut [Trit]) -> usize {
+    let mut pos = encoded_length_u64(values.len() as u64);
+    encode_u64(values.len() as u64, out);
+    for &v in values {
+        let end = pos + encoded_length_i64(v);
+        encode_i64(v, &mut out[pos..end]);
+        pos = end;
+    }
+    pos
+}
+
+/// Decodes a list of integers at the start of `input`, returning it along
+/// with the length of its encoding
+pub fn decode_list(input: &[Trit]) -> Result<(Vec<i64>, usize), PascalError> {
+    let (count, mut pos) = decode_u64(input)?;
+    // Every integer takes at least a tryte, so longer lists cannot fit
+    if count > (input.len() / TRITS_PER_TRYTE) as u64 {
+        return Err(PascalError::Truncated);
+    }
+    let mut values: Vec<i64> = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (v, length) = decode_i64(&input[pos..])?;
+        values.push(v);
+        pos += length;
+    }
+    Ok((values, pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::Vec;
+
+    #[test]
+    fn strings_and_lists_round_trip() {
+        let value: Vec<Trit> = vec![1, 0, -1, -1, 1, 0, 0];
+        let mut e: Vec<Trit> = vec![0; string_encoded_length(&value) + 2];
+        let length = encode_string(&value, &mut e);
+        assert_eq!(length, string_encoded_length(&value));
+        assert_eq!(decode_string(&e), Ok((&value[..], length)));
+        assert_eq!(decode_string(&e[..length - 1]), Err(PascalError::Truncated));
+
+        let values = [0, -1, 42, i64::min_value(), i64::max_value()];
+        let mut e: Vec<Trit> = vec![0; list_encoded_length(&values)];
+        let length = encode_list(&values, &mut e);
+        assert_eq!(length, e.len());
+        assert_eq!(decode_list(&e), Ok((values.to_vec(), length)));
+        assert_eq!(decode_list(&e[..length - 1]), Err(PascalError::Truncated));
+        assert_eq!(decode_list(&[]), Err(PascalError::Truncated));
+    }
+}
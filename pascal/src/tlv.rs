@@ -0,0 +1,151 @@
+//! Tag-length-value records on top of the pascal encoding
+//!
+//! A record is a pascal encoded tag followed by a pascal encoded trit
+//! string, so that readers can skip records with tags they do not know:
+//!
+//! ```text
+//! [Tag, Length, Value...]
+//! ```
+//!
+//! Records are simply concatenated, e.g. to make up a message.
+use trytes::constants::*;
+use errors::*;
+use wide::*;
+use sequence::*;
+
+/// A record read from its encoding
+#[derive(Eq, PartialEq, Debug)]
+pub struct Record<'a> {
+    pub tag: u64,
+    pub value: &'a [Trit],
+}
+
+impl<'a> Record<'a> {
+    /// Decodes the value as a single integer, which must fill all of it
+    pub fn as_i64(&self) -> Result<i64, PascalError> {
+        let (v, length) = decode_i64(self.value)?;
+        if length != self.value.len() {
+            return Err(PascalError::InvalidEncoding);
+        }
+        Ok(v)
+    }
+}
+
+/// Length of the record with a `value` of `value_length` trits
+pub fn record_encoded_length(tag: u64, value_length: usize) -> usize {
+    encoded_length_u64(tag) + encoded_length_u64(value_length as u64) + value_length
+}
+
+/// Encodes a record to the start of `out`, returning its length
+pub fn encode_record(tag: u64, value: &[Trit], out: &mut [Trit]) -> usize {
+    let start = encoded_length_u64(tag);
+    encode_u64(tag, out);
+    start + encode_string(value, &mut out[start..])
+}
+
+/// Encodes a record holding the integer `value` to the start of `out`,
+/// returning its length
+pub fn encode_i64_record(tag: u64, value: i64, out: &mut [Trit]) -> usize {
+    let tag_end = encoded_length_u64(tag);
+    let value_length = encoded_length_i64(value);
+    let value_start = tag_end + encoded_length_u64(value_length as u64);
+    encode_u64(tag, out);
+    encode_u64(value_length as u64, &mut out[tag_end..]);
+    encode_i64(value, &mut out[value_start..]);
+    value_start + value_length
+}
+
+/// Decodes the record at the start of `input`, returning it along with its
+/// length
+pub fn decode_record(input: &[Trit]) -> Result<(Record, usize), PascalError> {
+    let (tag, start) = decode_u64(input)?;
+    let (value, length) = decode_string(&input[start..])?;
+    Ok((
+        Record {
+            tag: tag,
+            value: value,
+        },
+        start + length,
+    ))
+}
+
+/// Iterator over the records making up some trits, stopping after the
+/// first error
+pub struct Records<'a> {
+    input: &'a [Trit],
+    failed: bool,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<Record<'a>, PascalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.input.is_empty() {
+            return None;
+        }
+        match decode_record(self.input) {
+            Ok((record, length)) => {
+                self.input = &self.input[length..];
+                Some(Ok(record))
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Iterates over the records making up `input`
+pub fn records(input: &[Trit]) -> Records {
+    Records {
+        input: input,
+        failed: false,
+    }
+}
+
+/// Finds the first record with the given `tag` in `input`
+pub fn find(input: &[Trit], tag: u64) -> Result<Option<Record>, PascalError> {
+    for record in records(input) {
+        let record = record?;
+        if record.tag == tag {
+            return Ok(Some(record));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::Vec;
+
+    const TEMPERATURE: u64 = 1;
+    const NAME: u64 = 2;
+
+    #[test]
+    fn records_round_trip() {
+        let name: Vec<Trit> = vec![1, 1, 0, -1, 0, 1];
+        let mut e: Vec<Trit> = vec![
+            0;
+            record_encoded_length(TEMPERATURE, encoded_length_i64(-21)) +
+                record_encoded_length(NAME, name.len())
+        ];
+        let pos = encode_i64_record(TEMPERATURE, -21, &mut e);
+        let end = pos + encode_record(NAME, &name, &mut e[pos..]);
+        assert_eq!(end, e.len());
+
+        let read: Vec<Record> = records(&e).map(|r| r.unwrap()).collect();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].tag, TEMPERATURE);
+        assert_eq!(read[0].as_i64(), Ok(-21));
+        assert_eq!(read[1], Record { tag: NAME, value: &name });
+        assert_eq!(find(&e, NAME), Ok(Some(Record { tag: NAME, value: &name })));
+        assert_eq!(find(&e, 3), Ok(None));
+
+        let mut iter = records(&e[..end - 1]);
+        assert!(iter.next().unwrap().is_ok());
+        assert_eq!(iter.next(), Some(Err(PascalError::Truncated)));
+        assert_eq!(iter.next(), None);
+    }
+}
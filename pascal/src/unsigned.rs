@@ -0,0 +1,150 @@
+//! Compact pascal encoding of unsigned integers
+//!
+//! Lengths, indices and counts are never negative, so they need neither the
+//! sign flips nor the encoder trits of `encode`. Instead, every tryte but the
+//! last holds a base 14 digit `d` as `-d`, and the last tryte holds the most
+//! significant digit `d` in `0..13` as `d + 1`. The end of the encoding is
+//! found like in `decode`, at the first positive tryte:
+//!
+//! ```text
+//! 0..13      -> 1 tryte
+//! 13..182    -> 2 trytes
+//! 182..2548  -> 3 trytes
+//! ```
+use trytes::constants::*;
+use trytes::num;
+use errors::*;
+
+/// Base of the digits held by all but the last tryte
+const BASE: u64 = 14;
+/// Number of digits the last tryte can hold
+const LAST_DIGITS: u64 = 13;
+
+fn write_tryte(value: isize, out: &mut [Trit]) {
+    let mut rest = value;
+    for t in out.iter_mut() {
+        let mut r = rest % RADIX as isize;
+        if r > 1 {
+            r -= RADIX as isize;
+        } else if r < -1 {
+            r += RADIX as isize;
+        }
+        *t = r as Trit;
+        rest = (rest - r) / RADIX as isize;
+    }
+}
+
+pub fn encoded_length_unsigned(input: u64) -> usize {
+    let mut length = TRITS_PER_TRYTE;
+    let mut rest = input;
+    while rest >= LAST_DIGITS {
+        rest /= BASE;
+        length += TRITS_PER_TRYTE;
+    }
+    length
+}
+
+pub fn encode_unsigned(input: u64, out: &mut [Trit]) {
+    let mut rest = input;
+    let mut pos = 0;
+    while rest >= LAST_DIGITS {
+        write_tryte(
+            -((rest % BASE) as isize),
+            &mut out[pos..pos + TRITS_PER_TRYTE],
+        );
+        rest /= BASE;
+        pos += TRITS_PER_TRYTE;
+    }
+    write_tryte(rest as isize + 1, &mut out[pos..pos + TRITS_PER_TRYTE]);
+}
+
+/// Decodes an unsigned integer at the start of `input`, returning it along
+/// with the length of its encoding.
+///
+/// Returns `PascalError::InvalidEncoding` for encodings longer than needed
+pub fn decode_unsigned(input: &[Trit]) -> Result<(u64, usize), PascalError> {
+    let mut end = 0;
+    let last = loop {
+        if end + TRITS_PER_TRYTE > input.len() {
+            return Err(PascalError::Truncated);
+        }
+        let v = num::trits2int(&input[end..end + TRITS_PER_TRYTE]) as i64;
+        end += TRITS_PER_TRYTE;
+        if v > 0 {
+            break v;
+        }
+    };
+    let mut value = (last - 1) as u64;
+    for tryte in input[..end - TRITS_PER_TRYTE].chunks(TRITS_PER_TRYTE).rev() {
+        let digit = -(num::trits2int(tryte) as i64) as u64;
+        value = value
+            .checked_mul(BASE)
+            .and_then(|v| v.checked_add(digit))
+            .ok_or(PascalError::Overflow)?;
+    }
+    if encoded_length_unsigned(value) != end {
+        return Err(PascalError::InvalidEncoding);
+    }
+    Ok((value, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pascal::*;
+    use alloc::Vec;
+
+    fn test_unsigned(i: u64) {
+        let mut e: Vec<Trit> = vec![0; encoded_length_unsigned(i)];
+        encode_unsigned(i, &mut e);
+        assert_eq!(decode_unsigned(&e), Ok((i, e.len())), "Output should match for {}", i);
+    }
+
+    #[test]
+    fn encodes_unsigned_numbers() {
+        let mut unsigned_length = 0;
+        let mut signed_length = 0;
+        for i in 0..3000 {
+            test_unsigned(i);
+            unsigned_length += encoded_length_unsigned(i);
+            signed_length += encoded_length(i as isize);
+        }
+        assert!(unsigned_length < signed_length);
+        assert_eq!(encoded_length_unsigned(12), 3);
+        assert_eq!(encoded_length_unsigned(13), 6);
+        assert_eq!(encoded_length_unsigned(181), 6);
+        assert_eq!(encoded_length_unsigned(182), 9);
+        test_unsigned(u64::max_value());
+        test_unsigned(u64::max_value() / BASE);
+    }
+
+    #[test]
+    fn rejects_invalid_encodings() {
+        assert_eq!(decode_unsigned(&[0, 0, 0, 1, 0, 0]), Err(PascalError::InvalidEncoding));
+        assert_eq!(decode_unsigned(&[0, 0, 0, 0, 0]), Err(PascalError::Truncated));
+        let mut e: Vec<Trit> = vec![0; encoded_length_unsigned(u64::max_value())];
+        encode_unsigned(u64::max_value(), &mut e);
+        let length = e.len();
+        e.extend_from_slice(&[0, 0, 0]);
+        e[length - 3..].clone_from_slice(&[-1, -1, -1, 1, 1, 1]);
+        assert_eq!(decode_unsigned(&e), Err(PascalError::Overflow));
+    }
+    quickcheck! {
+        fn round_trips_unsigned(value: u64, shift: u8) -> bool {
+            let i = value.wrapping_shl(shift as u32);
+            let mut e: Vec<Trit> = vec![0; encoded_length_unsigned(i)];
+            encode_unsigned(i, &mut e);
+            decode_unsigned(&e) == Ok((i, e.len())) &&
+                (0..e.len()).all(|end| decode_unsigned(&e[..end]).is_err())
+        }
+
+        fn flipped_trits_change_unsigned(value: u64, shift: u8, pos: usize) -> bool {
+            let i = value.wrapping_shl(shift as u32);
+            let mut e: Vec<Trit> = vec![0; encoded_length_unsigned(i)];
+            encode_unsigned(i, &mut e);
+            let pos = pos % e.len();
+            e[pos] = if e[pos] == 1 { -1 } else { e[pos] + 1 };
+            decode_unsigned(&e).map_or(true, |(decoded, _)| decoded != i)
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! Pascal encoding of `i64`, `u64` and integers of arbitrary width
+//!
+//! The encoding is the same as for `encode`, but all arithmetic on the
+//! encoder is done on big integers and decoding detects values which do not
+//! fit, so that results do not depend on the platform word size.
+//!
+//! Integers of arbitrary width are given as balanced trits, least
+//! significant first.
+use alloc::Vec;
+use trytes::constants::*;
+use trytes::num;
+use errors::*;
+use pascal::ZERO;
+
+/// Trits holding the balanced ternary value of any `i64` or `u64`
+const WORD_TRITS: usize = 42;
+
+/// A non-negative integer of little-endian 32 bit limbs
+struct Limbs(Vec<u32>);
+
+impl Limbs {
+    fn new() -> Self {
+        Limbs(Vec::new())
+    }
+
+    fn set_bit(&mut self, i: usize) {
+        while self.0.len() <= i / 32 {
+            self.0.push(0);
+        }
+        self.0[i / 32] |= 1 << (i % 32);
+    }
+
+    fn bit(&self, i: usize) -> bool {
+        self.0.get(i / 32).map_or(false, |l| (l >> (i % 32)) & 1 != 0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|&l| l == 0)
+    }
+
+    fn increment(&mut self) {
+        for l in self.0.iter_mut() {
+            let (sum, carry) = l.overflowing_add(1);
+            *l = sum;
+            if !carry {
+                return;
+            }
+        }
+        self.0.push(1);
+    }
+
+    /// Sets `self` to `self * 3 + trit`, returning false if it would be negative
+    fn mul3_add(&mut self, trit: Trit) -> bool {
+        let mut carry = 0u64;
+        for l in self.0.iter_mut() {
+            let v = *l as u64 * 3 + carry;
+            *l = v as u32;
+            carry = v >> 32;
+        }
+        if carry != 0 {
+            self.0.push(carry as u32);
+        }
+        match trit {
+            1 => self.increment(),
+            -1 => {
+                if self.is_zero() {
+                    return false;
+                }
+                for l in self.0.iter_mut() {
+                    let (diff, borrow) = l.overflowing_sub(1);
+                    *l = diff;
+                    if !borrow {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Divides `self` by 3, returning the remainder
+    fn divmod3(&mut self) -> u64 {
+        let mut rem = 0u64;
+        for l in self.0.iter_mut().rev() {
+            let v = (rem << 32) | *l as u64;
+            *l = (v / 3) as u32;
+            rem = v % 3;
+        }
+        rem
+    }
+
+    /// Consumes `self`, returning its minimal balanced trits
+    fn into_trits(mut self) -> Vec<Trit> {
+        let mut trits = Vec::new();
+        while !self.is_zero() {
+            match self.divmod3() {
+                2 => {
+                    trits.push(-1);
+                    self.increment();
+                }
+                r => trits.push(r as Trit),
+            }
+        }
+        trits
+    }
+}
+
+/// Length of the encoder of a value of `trytes` trytes, i.e. the trits
+/// needed for a bitmask of `trytes` ones
+fn encoder_length(trytes: usize) -> usize {
+    let mut mask = Limbs::new();
+    for i in 0..trytes {
+        mask.set_bit(i);
+    }
+    mask.into_trits().len()
+}
+
+fn significant_length(value: &[Trit]) -> usize {
+    value.iter().rposition(|&t| t != 0).map_or(0, |i| i + 1)
+}
+
+fn value_length(value: &[Trit]) -> usize {
+    let length = significant_length(value);
+    (length + TRITS_PER_TRYTE - 1) / TRITS_PER_TRYTE * TRITS_PER_TRYTE
+}
+
+/// Length of the encoding of the balanced trits `value`
+pub fn encoded_length_trits(value: &[Trit]) -> usize {
+    let length = value_length(value);
+    if length == 0 {
+        ZERO.len()
+    } else {
+        length + encoder_length(length / TRITS_PER_TRYTE)
+    }
+}
+
+/// Encodes the balanced trits `value` to the start of `out`
+pub fn encode_trits(value: &[Trit], out: &mut [Trit]) {
+    let length = value_length(value);
+    if length == 0 {
+        out[..ZERO.len()].clone_from_slice(&ZERO);
+        return;
+    }
+    let significant = significant_length(value);
+    let trytes = length / TRITS_PER_TRYTE;
+    let end = length + encoder_length(trytes);
+
+    out[..significant].clone_from_slice(&value[..significant]);
+    for t in out[significant..end].iter_mut() {
+        *t = 0;
+    }
+    let mut encoder = Limbs::new();
+    for (i, tryte) in out[..length].chunks_mut(TRITS_PER_TRYTE).enumerate() {
+        let v = num::trits2int(tryte);
+        let flip = if i + 1 < trytes {
+            v.is_positive()
+        } else {
+            v.is_negative()
+        };
+        if flip {
+            encoder.set_bit(i);
+            for t in tryte.iter_mut() {
+                *t = -*t;
+            }
+        }
+    }
+    let encoder = encoder.into_trits();
+    out[length..length + encoder.len()].clone_from_slice(&encoder);
+}
+
+/// Decodes an encoding at the start of `input` into the balanced trits of
+/// `out`, returning the length of the encoding.
+///
+/// Returns `PascalError::Overflow` if the value needs more trits than `out`
+/// holds
+pub fn decode_trits(input: &[Trit], out: &mut [Trit]) -> Result<usize, PascalError> {
+    if input.len() < ZERO.len() {
+        return Err(PascalError::Truncated);
+    }
+    if input[..ZERO.len()] == ZERO {
+        for t in out.iter_mut() {
+            *t = 0;
+        }
+        return Ok(ZERO.len());
+    }
+    let mut encoders_start = 0;
+    loop {
+        if encoders_start + TRITS_PER_TRYTE > input.len() {
+            return Err(PascalError::Truncated);
+        }
+        let v = num::trits2int(&input[encoders_start..encoders_start + TRITS_PER_TRYTE]);
+        encoders_start += TRITS_PER_TRYTE;
+        if v.is_positive() {
+            break;
+        }
+    }
+    let input_end = encoders_start + encoder_length(encoders_start / TRITS_PER_TRYTE);
+    if input_end > input.len() {
+        return Err(PascalError::Truncated);
+    }
+    let mut encoder = Limbs::new();
+    for &t in input[encoders_start..input_end].iter().rev() {
+        if !encoder.mul3_add(t) {
+            return Err(PascalError::InvalidEncoding);
+        }
+    }
+    let significant = significant_length(&input[..encoders_start]);
+    if significant > out.len() {
+        return Err(PascalError::Overflow);
+    }
+    for (i, t) in out.iter_mut().enumerate() {
+        *t = if i >= significant {
+            0
+        } else if encoder.bit(i / TRITS_PER_TRYTE) {
+            -input[i]
+        } else {
+            input[i]
+        };
+    }
+    Ok(input_end)
+}
+
+fn word_to_trits(magnitude: u64, negative: bool, out: &mut [Trit]) {
+    let mut rest = magnitude;
+    for t in out.iter_mut() {
+        let r = match rest % 3 {
+            2 => -1,
+            r => r as Trit,
+        };
+        rest = rest / 3 + if r == -1 { 1 } else { 0 };
+        *t = if negative { -r } else { r };
+    }
+}
+
+/// Returns the sign and magnitude of balanced trits, if the magnitude fits
+fn trits_to_word(trits: &[Trit]) -> Result<(bool, u64), PascalError> {
+    let significant = significant_length(trits);
+    if significant == 0 {
+        return Ok((false, 0));
+    }
+    let negative = trits[significant - 1] < 0;
+    let mut magnitude = 0u64;
+    for &t in trits[..significant].iter().rev() {
+        let digit = if negative { -t } else { t };
+        magnitude = match digit {
+            // m * 3 - 1 == (m - 1) * 3 + 2, which cannot overflow before the result does
+            -1 => (magnitude - 1).checked_mul(3).and_then(|m| m.checked_add(2)),
+            d => magnitude.checked_mul(3).and_then(|m| m.checked_add(d as u64)),
+        }.ok_or(PascalError::Overflow)?;
+    }
+    Ok((negative, magnitude))
+}
+
+fn i64_to_trits(input: i64) -> [Trit; WORD_TRITS] {
+    let mut trits: [Trit; WORD_TRITS] = [0; WORD_TRITS];
+    word_to_trits(input.wrapping_abs() as u64, input < 0, &mut trits);
+    trits
+}
+
+fn u64_to_trits(input: u64) -> [Trit; WORD_TRITS] {
+    let mut trits: [Trit; WORD_TRITS] = [0; WORD_TRITS];
+    word_to_trits(input, false, &mut trits);
+    trits
+}
+
+pub fn encoded_length_i64(input: i64) -> usize {
+    encoded_length_trits(&i64_to_trits(input))
+}
+
+pub fn encode_i64(input: i64, out: &mut [Trit]) {
+    encode_trits(&i64_to_trits(input), out);
+}
+
+/// Decodes an `i64` at the start of `input`, returning it along with the
+/// length of its encoding, or `PascalError::Overflow` if it does not fit
+pub fn decode_i64(input: &[Trit]) -> Result<(i64, usize), PascalError> {
+    let mut trits: [Trit; WORD_TRITS] = [0; WORD_TRITS];
+    let length = decode_trits(input, &mut trits)?;
+    match trits_to_word(&trits)? {
+        (true, m) if m <= 1 << 63 => Ok(((m as i64).wrapping_neg(), length)),
+        (false, m) if m < 1 << 63 => Ok((m as i64, length)),
+        _ => Err(PascalError::Overflow),
+    }
+}
+
+pub fn encoded_length_u64(input: u64) -> usize {
+    encoded_length_trits(&u64_to_trits(input))
+}
+
+pub fn encode_u64(input: u64, out: &mut [Trit]) {
+    encode_trits(&u64_to_trits(input), out);
+}
+
+/// Decodes a `u64` at the start of `input`, returning it along with the
+/// length of its encoding, or `PascalError::Overflow` if it is negative or
+/// does not fit
+pub fn decode_u64(input: &[Trit]) -> Result<(u64, usize), PascalError> {
+    let mut trits: [Trit; WORD_TRITS] = [0; WORD_TRITS];
+    let length = decode_trits(input, &mut trits)?;
+    match trits_to_word(&trits)? {
+        (false, m) => Ok((m, length)),
+        (true, _) => Err(PascalError::Overflow),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pascal::*;
+    use alloc::Vec;
+
+    fn test_i64(i: i64) {
+        let mut e: Vec<Trit> = vec![0; encoded_length_i64(i)];
+        encode_i64(i, &mut e);
+        assert_eq!(decode_i64(&e), Ok((i, e.len())), "Output should match for {}", i);
+    }
+
+    fn test_u64(i: u64) {
+        let mut e: Vec<Trit> = vec![0; encoded_length_u64(i)];
+        encode_u64(i, &mut e);
+        assert_eq!(decode_u64(&e), Ok((i, e.len())), "Output should match for {}", i);
+    }
+
+    #[test]
+    fn encodes_full_range() {
+        for &i in [0, 1, -1, 13, 14, -13, -14, 364, 365, i64::max_value(), i64::min_value()].iter() {
+            test_i64(i);
+            test_i64(i / 3);
+            test_i64(i / 27);
+        }
+        let mut p: u64 = 1;
+        while let Some(next) = p.checked_mul(3) {
+            test_u64(p - 1);
+            test_u64(p);
+            test_u64(p + 1);
+            test_i64(p as i64);
+            test_i64(-(p as i64));
+            p = next;
+        }
+        test_u64(u64::max_value());
+        test_u64(u64::max_value() - 1);
+
+        // Walk the whole range with a fixed stride
+        let mut x: u64 = 0x9E37_79B9_7F4A_7C15;
+        for _ in 0..1000 {
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            test_u64(x);
+            test_i64(x as i64);
+        }
+    }
+
+    #[test]
+    fn matches_isize_encoding() {
+        for i in -1000..1000 {
+            let mut e: Vec<Trit> = vec![0; encoded_length(i)];
+            let mut w: Vec<Trit> = vec![0; encoded_length_i64(i as i64)];
+            encode(i, &mut e);
+            encode_i64(i as i64, &mut w);
+            assert_eq!(e, w, "Encoding should match for {}", i);
+        }
+    }
+
+    #[test]
+    fn detects_overflow() {
+        let mut e: Vec<Trit> = vec![0; encoded_length_u64(u64::max_value())];
+        encode_u64(u64::max_value(), &mut e);
+        assert_eq!(decode_i64(&e), Err(PascalError::Overflow));
+
+        let mut e: Vec<Trit> = vec![0; encoded_length_i64(-1)];
+        encode_i64(-1, &mut e);
+        assert_eq!(decode_u64(&e), Err(PascalError::Overflow));
+
+        let value: Vec<Trit> = (0..100).map(|i| (i % 3) as Trit - 1).collect();
+        let mut e: Vec<Trit> = vec![0; encoded_length_trits(&value)];
+        encode_trits(&value, &mut e);
+        assert_eq!(decode_i64(&e), Err(PascalError::Overflow));
+        let mut out: [Trit; 99] = [0; 99];
+        assert_eq!(decode_trits(&e, &mut out), Err(PascalError::Overflow));
+        let mut out: [Trit; 102] = [0; 102];
+        assert_eq!(decode_trits(&e, &mut out), Ok(e.len()));
+        assert_eq!(&out[..100], &value[..]);
+        assert_eq!(&out[100..], &[0, 0]);
+
+        assert_eq!(decode_trits(&e[..e.len() - 1], &mut out), Err(PascalError::Truncated));
+        assert_eq!(decode_i64(&[-1, 0, 0]), Err(PascalError::Truncated));
+    }
+    quickcheck! {
+        fn round_trips_i64(value: i64, shift: u8) -> bool {
+            let i = value.wrapping_shl(shift as u32);
+            let mut e: Vec<Trit> = vec![0; encoded_length_i64(i)];
+            encode_i64(i, &mut e);
+            decode_i64(&e) == Ok((i, e.len())) &&
+                (0..e.len()).all(|end| decode_i64(&e[..end]).is_err())
+        }
+
+        fn round_trips_u64(value: u64, shift: u8) -> bool {
+            let i = value.wrapping_shl(shift as u32);
+            let mut e: Vec<Trit> = vec![0; encoded_length_u64(i)];
+            encode_u64(i, &mut e);
+            decode_u64(&e) == Ok((i, e.len())) &&
+                (0..e.len()).all(|end| decode_u64(&e[..end]).is_err())
+        }
+
+        fn round_trips_trits(digits: Vec<u8>) -> bool {
+            let value: Vec<Trit> = digits.iter().map(|&d| (d % 3) as Trit - 1).collect();
+            let mut e: Vec<Trit> = vec![0; encoded_length_trits(&value)];
+            encode_trits(&value, &mut e);
+            let mut out: Vec<Trit> = vec![0; value.len()];
+            decode_trits(&e, &mut out) == Ok(e.len()) && out == value
+        }
+    }
+}